@@ -8,19 +8,24 @@ use async_trait::async_trait;
 use bytes::Bytes;
 use creep::Context;
 use crossbeam_channel::{unbounded, Receiver, Sender};
+use futures::executor::{block_on, ThreadPool};
 use hasher::{Hasher, HasherKeccak};
 use lazy_static::lazy_static;
 use rand::random;
 use serde::{Deserialize, Serialize};
 
 use overlord::types::{AggregatedSignature, Commit, Hash, Node, OverlordMsg, Status};
-use overlord::{Codec, Consensus, Crypto, DurationConfig, Overlord, OverlordHandler};
+use overlord::wal::FileWalStorage;
+use overlord::{Codec, Consensus, Crypto, DurationConfig, Overlord, OverlordConfig};
+use overlord::{OverlordHandler, Spawner};
 
 lazy_static! {
     static ref HASHER_INST: HasherKeccak = HasherKeccak::new();
 }
 
 const SPEAKER_NUM: u8 = 20;
+const WAL_RETENTION: u64 = 10;
+const MSG_CAPACITY: usize = 1024;
 
 const SPEECH_INTERVAL: u64 = 1000; // ms
 
@@ -178,6 +183,7 @@ impl Consensus<Speech, Detail> for Brain {
         Ok(Status {
             epoch_id:       epoch_id + 1,
             interval:       Some(SPEECH_INTERVAL),
+            timer_config:   None,
             authority_list: self.speaker_list.clone(),
         })
     }
@@ -213,7 +219,7 @@ impl Consensus<Speech, Detail> for Brain {
 }
 
 struct Speaker {
-    overlord: Arc<Overlord<Speech, Detail, Brain, MockCrypto>>,
+    overlord: Arc<Overlord<Speech, Detail, Brain, MockCrypto, FileWalStorage>>,
     handler:  OverlordHandler<Speech>,
     brain:    Arc<Brain>,
 }
@@ -225,6 +231,7 @@ impl Speaker {
         talk_to: HashMap<Bytes, Sender<OverlordMsg<Speech>>>,
         hearing: Receiver<OverlordMsg<Speech>>,
         consensus_speech: Arc<Mutex<HashMap<u64, Bytes>>>,
+        spawner: Arc<dyn Spawner>,
     ) -> Self {
         let crypto = MockCrypto::new(name.clone());
         let brain = Arc::new(Brain::new(
@@ -233,15 +240,18 @@ impl Speaker {
             hearing,
             consensus_speech,
         ));
-        let overlord = Overlord::new(name, Arc::clone(&brain), crypto);
+        let wal_path = format!("./logs/wal/{}", hex::encode(name.clone()));
+        let wal = Arc::new(FileWalStorage::new(&wal_path, WAL_RETENTION));
+        let overlord = Overlord::new(name, Arc::clone(&brain), crypto, wal, spawner);
         let overlord_handler = overlord.get_handler();
 
         overlord_handler
-            .send_msg(
+            .try_send_msg(
                 Context::new(),
                 OverlordMsg::RichStatus(Status {
                     epoch_id:       1,
                     interval:       Some(SPEECH_INTERVAL),
+                    timer_config:   None,
                     authority_list: speaker_list,
                 }),
             )
@@ -254,38 +264,24 @@ impl Speaker {
         }
     }
 
-    async fn run(
-        &self,
-        interval: u64,
-        timer_config: Option<DurationConfig>,
-    ) -> Result<(), Box<dyn Error + Send>> {
+    async fn run(&self, config: OverlordConfig) -> Result<(), Box<dyn Error + Send>> {
         let brain = Arc::<Brain>::clone(&self.brain);
         let handler = self.handler.clone();
 
         thread::spawn(move || loop {
             if let Ok(msg) = brain.hearing.recv() {
                 match msg {
-                    OverlordMsg::SignedVote(vote) => {
-                        handler
-                            .send_msg(Context::new(), OverlordMsg::SignedVote(vote))
-                            .unwrap();
-                    }
-                    OverlordMsg::SignedProposal(proposal) => {
-                        handler
-                            .send_msg(Context::new(), OverlordMsg::SignedProposal(proposal))
-                            .unwrap();
-                    }
-                    OverlordMsg::AggregatedVote(agg_vote) => {
-                        handler
-                            .send_msg(Context::new(), OverlordMsg::AggregatedVote(agg_vote))
-                            .unwrap();
+                    OverlordMsg::SignedVote(_)
+                    | OverlordMsg::SignedProposal(_)
+                    | OverlordMsg::AggregatedVote(_) => {
+                        block_on(handler.send_msg(Context::new(), msg)).unwrap();
                     }
                     _ => {}
                 }
             }
         });
 
-        self.overlord.run(interval, timer_config).await.unwrap();
+        self.overlord.run(config).await.unwrap();
 
         Ok(())
     }
@@ -303,6 +299,7 @@ async fn main() {
         .zip(channels.iter().map(|(_, receiver)| receiver.clone()))
         .collect();
     let consensus_speech = Arc::new(Mutex::new(HashMap::new()));
+    let pool = ThreadPool::new().unwrap();
 
     let speaker_list_clone = speaker_list.clone();
 
@@ -321,9 +318,10 @@ async fn main() {
             talk_to,
             hearings.get(&name).unwrap().clone(),
             Arc::<Mutex<HashMap<u64, Bytes>>>::clone(&consensus_speech),
+            Arc::new(pool.clone()),
         ));
         runtime::spawn(async move {
-            speaker.run(SPEECH_INTERVAL, timer_config()).await.unwrap();
+            speaker.run(overlord_config()).await.unwrap();
         });
     }
 
@@ -341,6 +339,11 @@ fn hash(bytes: &Bytes) -> Bytes {
     Bytes::from(&out[..])
 }
 
-fn timer_config() -> Option<DurationConfig> {
-    Some(DurationConfig::new(10, 10, 10))
+fn overlord_config() -> OverlordConfig {
+    OverlordConfig::builder()
+        .interval(SPEECH_INTERVAL)
+        .duration(DurationConfig::new(10, 10, 10))
+        .msg_capacity(MSG_CAPACITY)
+        .build()
+        .unwrap()
 }
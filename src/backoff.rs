@@ -0,0 +1,137 @@
+use std::fmt::Debug;
+use std::time::Duration;
+
+use serde::{Deserialize, Serialize};
+
+/// The upper bound of a step timeout after backoff, which is one day. It prevents the timeout
+/// from overflowing the timer.
+const MAX_TIMEOUT: u64 = 24 * 60 * 60 * 1000;
+
+/// The backoff policy of a step timeout, which is consulted by the timer for every step. It
+/// extends the timeout as the round increasing, so that the nodes are able to catch up with each
+/// other after failed rounds.
+pub trait BackoffPolicy: Debug + Send + Sync {
+    /// Get the timeout of the given round from the base timeout of the step.
+    fn backoff(&self, base: Duration, round: u64) -> Duration;
+}
+
+/// Never extend the timeout.
+#[derive(Clone, Debug, Default)]
+pub struct NoBackoff;
+
+impl BackoffPolicy for NoBackoff {
+    fn backoff(&self, base: Duration, _round: u64) -> Duration {
+        base
+    }
+}
+
+/// Extend the timeout linearly, which is `base * (round + 1)`.
+#[derive(Clone, Debug, Default)]
+pub struct LinearBackoff;
+
+impl BackoffPolicy for LinearBackoff {
+    fn backoff(&self, base: Duration, round: u64) -> Duration {
+        saturating_backoff(base, round.saturating_add(1))
+    }
+}
+
+/// Double the timeout each round, which is `base * 2^round`.
+#[derive(Clone, Debug, Default)]
+pub struct ExponentialBackoff;
+
+impl BackoffPolicy for ExponentialBackoff {
+    fn backoff(&self, base: Duration, round: u64) -> Duration {
+        saturating_backoff(base, pow2(round))
+    }
+}
+
+/// Double the timeout each round until the round reaches the cap, which is
+/// `base * 2^min(round, max_exp)`.
+#[derive(Clone, Debug)]
+pub struct CappedExponentialBackoff {
+    max_exp: u32,
+}
+
+impl CappedExponentialBackoff {
+    /// Create a capped exponential backoff with the cap of the exponent.
+    pub fn new(max_exp: u32) -> Self {
+        CappedExponentialBackoff { max_exp }
+    }
+}
+
+impl BackoffPolicy for CappedExponentialBackoff {
+    fn backoff(&self, base: Duration, round: u64) -> Duration {
+        saturating_backoff(base, pow2(round.min(u64::from(self.max_exp))))
+    }
+}
+
+/// The configuration of a step backoff policy.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+pub enum BackoffConfig {
+    /// Never extend the timeout.
+    None,
+    /// Extend the timeout linearly.
+    Linear,
+    /// Double the timeout each round.
+    Exponential,
+    /// Double the timeout each round, and the exponent is capped at the given value.
+    CappedExponential(u32),
+}
+
+impl BackoffConfig {
+    /// Build the backoff policy of the configuration.
+    pub fn build(&self) -> Box<dyn BackoffPolicy> {
+        match self {
+            BackoffConfig::None => Box::new(NoBackoff),
+            BackoffConfig::Linear => Box::new(LinearBackoff),
+            BackoffConfig::Exponential => Box::new(ExponentialBackoff),
+            BackoffConfig::CappedExponential(max_exp) => {
+                Box::new(CappedExponentialBackoff::new(*max_exp))
+            }
+        }
+    }
+}
+
+fn pow2(exp: u64) -> u64 {
+    if exp >= 64 {
+        u64::max_value()
+    } else {
+        1u64 << exp
+    }
+}
+
+fn saturating_backoff(base: Duration, coef: u64) -> Duration {
+    let millis = (base.as_millis() as u64).saturating_mul(coef);
+    Duration::from_millis(millis.min(MAX_TIMEOUT))
+}
+
+#[cfg(test)]
+mod test {
+    use std::time::Duration;
+
+    use crate::backoff::{BackoffConfig, BackoffPolicy, MAX_TIMEOUT};
+
+    #[test]
+    fn test_backoff() {
+        let base = Duration::from_millis(100);
+        let test_cases = vec![
+            (BackoffConfig::None, 5, 100),
+            (BackoffConfig::Linear, 0, 100),
+            (BackoffConfig::Linear, 5, 600),
+            (BackoffConfig::Exponential, 5, 3200),
+            (BackoffConfig::Exponential, 100, MAX_TIMEOUT),
+            (BackoffConfig::CappedExponential(3), 2, 400),
+            (BackoffConfig::CappedExponential(3), 5, 800),
+            (
+                BackoffConfig::CappedExponential(64),
+                u64::max_value(),
+                MAX_TIMEOUT,
+            ),
+        ];
+
+        for (config, round, timeout) in test_cases.into_iter() {
+            let policy: Box<dyn BackoffPolicy> = config.build();
+            assert_eq!(policy.backoff(base, round), Duration::from_millis(timeout));
+        }
+    }
+}
@@ -0,0 +1,238 @@
+use std::env;
+use std::error::Error;
+use std::process;
+
+use bytes::Bytes;
+use serde_json::json;
+
+use overlord::error::ConsensusError;
+use overlord::types::{
+    AggregatedVote, Commit, Hash, LastSigned, SignedProposal, SignedVote, VerifyResp,
+};
+use overlord::wal::{FileWalStorage, WalFrame, WalMsgType, WalRecord};
+use overlord::{Codec, ConsensusResult, SMRStatus};
+
+const USAGE: &str = "Usage: overlord_wal <WAL_DIR> [--epoch EPOCH_ID] [--truncate-after INDEX]
+
+Print every frame in the write ahead log as a JSON line without modifying it. An invalid frame
+is printed with the error.
+
+Options:
+    --epoch EPOCH_ID         Only print the frames that saved in the given epoch.
+    --truncate-after INDEX   Remove the frames after the given frame index, including the invalid
+                             ones.";
+
+/// The content of proposals and commits is kept as raw bytes, since the tool does not know the
+/// content type.
+#[derive(Clone, Debug, PartialEq, Eq)]
+struct Raw(Bytes);
+
+impl Codec for Raw {
+    fn encode(&self) -> Result<Bytes, Box<dyn Error + Send>> {
+        Ok(self.0.clone())
+    }
+
+    fn decode(data: Bytes) -> Result<Self, Box<dyn Error + Send>> {
+        Ok(Raw(data))
+    }
+}
+
+#[derive(Debug, Default)]
+struct Args {
+    path:           String,
+    epoch_id:       Option<u64>,
+    truncate_after: Option<usize>,
+}
+
+/// The decoded fields of a record. The fields that a message type does not have are `None`.
+#[derive(Debug, Default)]
+struct RecordInfo {
+    epoch_id: Option<u64>,
+    round:    Option<u64>,
+    hash:     Option<Hash>,
+    voter:    Option<Bytes>,
+    proposer: Option<Bytes>,
+}
+
+fn main() {
+    let args = parse_args().unwrap_or_else(|err| {
+        eprintln!("{}\n\n{}", err, USAGE);
+        process::exit(1);
+    });
+
+    if let Err(err) = run(args) {
+        eprintln!("Error: {}", err);
+        process::exit(1);
+    }
+}
+
+fn run(args: Args) -> ConsensusResult<()> {
+    let storage = FileWalStorage::new(&args.path, 0);
+    let frames = storage
+        .load_frames()
+        .map_err(|err| ConsensusError::WalErr(format!("load error {:?}", err)))?;
+
+    for (index, frame) in frames.iter().enumerate() {
+        if let Some(epoch_id) = args.epoch_id {
+            if frame.epoch_id != epoch_id {
+                continue;
+            }
+        }
+
+        match to_json(index, frame) {
+            Ok(line) => println!("{}", line),
+            Err(err) => println!("{}", error_json(index, frame, &err)),
+        }
+    }
+
+    if let Some(index) = args.truncate_after {
+        if let Some(frame) = frames.get(index + 1) {
+            storage
+                .truncate(frame.epoch_id, frame.offset)
+                .map_err(|err| ConsensusError::WalErr(format!("truncate error {:?}", err)))?;
+            eprintln!(
+                "Truncate from offset {} of epoch {}",
+                frame.offset, frame.epoch_id
+            );
+        }
+    }
+    Ok(())
+}
+
+fn parse_args() -> Result<Args, String> {
+    let mut args = Args::default();
+    let mut iter = env::args().skip(1);
+
+    while let Some(arg) = iter.next() {
+        match arg.as_str() {
+            "--epoch" => args.epoch_id = Some(parse_value(&arg, iter.next())?),
+            "--truncate-after" => args.truncate_after = Some(parse_value(&arg, iter.next())?),
+            "-h" | "--help" => return Err(String::new()),
+            _ if args.path.is_empty() && !arg.starts_with('-') => args.path = arg,
+            _ => return Err(format!("Unexpected argument {}", arg)),
+        }
+    }
+
+    if args.path.is_empty() {
+        return Err("Missing the Wal directory".to_string());
+    }
+    Ok(args)
+}
+
+fn parse_value<T: std::str::FromStr>(name: &str, value: Option<String>) -> Result<T, String> {
+    value
+        .ok_or_else(|| format!("Missing the value of {}", name))?
+        .parse::<T>()
+        .map_err(|_| format!("Invalid value of {}", name))
+}
+
+fn to_json(index: usize, frame: &WalFrame) -> ConsensusResult<String> {
+    let data = frame
+        .record
+        .as_ref()
+        .map_err(|reason| ConsensusError::WalCorrupted {
+            offset: frame.offset,
+            reason: reason.clone(),
+        })?;
+    let record: WalRecord = rlp::decode(data)
+        .map_err(|err| ConsensusError::WalErr(format!("decode record error {:?}", err)))?;
+    let info = decode_record(&record)?;
+    let value = json!({
+        "index": index,
+        "offset": frame.offset,
+        "type": record.msg_type.to_string(),
+        "record_epoch": record.epoch_id,
+        "epoch": info.epoch_id,
+        "round": info.round,
+        "hash": info.hash.map(hex::encode),
+        "voter": info.voter.map(hex::encode),
+        "proposer": info.proposer.map(hex::encode),
+    });
+    Ok(value.to_string())
+}
+
+fn error_json(index: usize, frame: &WalFrame, err: &ConsensusError) -> String {
+    let value = json!({
+        "index": index,
+        "offset": frame.offset,
+        "record_epoch": frame.epoch_id,
+        "error": err.to_string(),
+    });
+    value.to_string()
+}
+
+fn decode_record(record: &WalRecord) -> ConsensusResult<RecordInfo> {
+    let info = match record.msg_type {
+        WalMsgType::SignedProposal => {
+            let proposal = record.decode::<SignedProposal<Raw>>()?.proposal;
+            RecordInfo {
+                epoch_id: Some(proposal.epoch_id),
+                round:    Some(proposal.round),
+                hash:     Some(proposal.epoch_hash),
+                voter:    None,
+                proposer: Some(proposal.proposer),
+            }
+        }
+        WalMsgType::SignedVote => {
+            let vote = record.decode::<SignedVote>()?.vote;
+            RecordInfo {
+                epoch_id: Some(vote.epoch_id),
+                round:    Some(vote.round),
+                hash:     Some(vote.epoch_hash),
+                voter:    Some(vote.voter),
+                proposer: None,
+            }
+        }
+        WalMsgType::AggregatedVote => {
+            let qc = record.decode::<AggregatedVote>()?;
+            RecordInfo {
+                epoch_id: Some(qc.epoch_id),
+                round:    Some(qc.round),
+                hash:     Some(qc.epoch_hash),
+                voter:    None,
+                proposer: Some(qc.leader),
+            }
+        }
+        WalMsgType::Commit => {
+            let proof = record.decode::<Commit<Raw>>()?.proof;
+            RecordInfo {
+                epoch_id: Some(proof.epoch_id),
+                round:    Some(proof.round),
+                hash:     Some(proof.epoch_hash),
+                voter:    None,
+                proposer: None,
+            }
+        }
+        WalMsgType::SMRStatus => {
+            let status = record.decode::<SMRStatus>()?;
+            RecordInfo {
+                epoch_id: Some(status.epoch_id),
+                round:    Some(status.round),
+                hash:     Some(status.epoch_hash),
+                voter:    None,
+                proposer: None,
+            }
+        }
+        WalMsgType::CheckEpoch => {
+            let resp = record.decode::<VerifyResp>()?;
+            RecordInfo {
+                epoch_id: Some(resp.epoch_id),
+                round:    None,
+                hash:     Some(resp.epoch_hash),
+                voter:    None,
+                proposer: None,
+            }
+        }
+        WalMsgType::LastSigned => {
+            let last_signed = record.decode::<LastSigned>()?;
+            RecordInfo {
+                epoch_id: Some(last_signed.epoch_id),
+                round:    Some(last_signed.round),
+                hash:     Some(last_signed.epoch_hash),
+                voter:    None,
+                proposer: None,
+            }
+        }
+    };
+    Ok(info)
+}
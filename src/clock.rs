@@ -0,0 +1,141 @@
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use futures::channel::oneshot;
+use futures::future::{ready, BoxFuture};
+use futures::FutureExt;
+use futures_timer::Delay;
+use parking_lot::Mutex;
+
+use crate::Clock;
+
+/// The clock of the system time, which is the default clock.
+#[derive(Clone, Debug, Default)]
+pub struct SystemClock;
+
+impl Clock for SystemClock {
+    fn now(&self) -> Instant {
+        Instant::now()
+    }
+
+    fn delay(&self, duration: Duration) -> BoxFuture<'static, ()> {
+        Delay::new(duration).boxed()
+    }
+}
+
+/// A virtual clock that only goes forward by `advance`, so that the timeouts can be driven
+/// deterministically in tests. The delays are completed in the order of their deadlines when the
+/// clock is advanced over them.
+#[derive(Clone, Debug)]
+pub struct VirtualClock {
+    inner: Arc<Mutex<VirtualTime>>,
+}
+
+#[derive(Debug)]
+struct VirtualTime {
+    start:   Instant,
+    elapsed: Duration,
+    timers:  Vec<(Duration, oneshot::Sender<()>)>,
+}
+
+impl Default for VirtualClock {
+    fn default() -> Self {
+        VirtualClock::new()
+    }
+}
+
+impl VirtualClock {
+    /// Create a new virtual clock which starts from the current instant.
+    pub fn new() -> Self {
+        let time = VirtualTime {
+            start:   Instant::now(),
+            elapsed: Duration::from_millis(0),
+            timers:  Vec::new(),
+        };
+
+        VirtualClock {
+            inner: Arc::new(Mutex::new(time)),
+        }
+    }
+
+    /// Advance the clock by the given duration, and complete the delays that expire.
+    pub fn advance(&self, duration: Duration) {
+        let mut expired = {
+            let mut time = self.inner.lock();
+            time.elapsed += duration;
+            let elapsed = time.elapsed;
+            let (expired, pending) = time
+                .timers
+                .drain(..)
+                .partition::<Vec<_>, _>(|(deadline, _)| *deadline <= elapsed);
+            time.timers = pending;
+            expired
+        };
+
+        expired.sort_by_key(|(deadline, _)| *deadline);
+        for (_, tx) in expired.into_iter() {
+            // The delay may have been dropped, ignore the error.
+            let _ = tx.send(());
+        }
+    }
+
+    /// Get the number of the pending delays, the dropped delays are not counted.
+    pub fn pending_delays(&self) -> usize {
+        let mut time = self.inner.lock();
+        time.timers.retain(|(_, tx)| !tx.is_canceled());
+        time.timers.len()
+    }
+}
+
+impl Clock for VirtualClock {
+    fn now(&self) -> Instant {
+        let time = self.inner.lock();
+        time.start + time.elapsed
+    }
+
+    fn delay(&self, duration: Duration) -> BoxFuture<'static, ()> {
+        if duration == Duration::from_millis(0) {
+            return ready(()).boxed();
+        }
+
+        let (tx, rx) = oneshot::channel();
+        let mut time = self.inner.lock();
+        let deadline = time.elapsed + duration;
+        time.timers.push((deadline, tx));
+        rx.map(|_| ()).boxed()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::time::Duration;
+
+    use futures::FutureExt;
+
+    use crate::clock::VirtualClock;
+    use crate::Clock;
+
+    #[test]
+    fn test_virtual_clock() {
+        let clock = VirtualClock::new();
+        let start = clock.now();
+        let mut short = clock.delay(Duration::from_millis(100));
+        let mut long = clock.delay(Duration::from_millis(300));
+        assert_eq!(clock.pending_delays(), 2);
+        assert!(short.as_mut().now_or_never().is_none());
+
+        clock.advance(Duration::from_millis(200));
+        assert_eq!(clock.now() - start, Duration::from_millis(200));
+        assert_eq!(clock.pending_delays(), 1);
+        assert!(short.now_or_never().is_some());
+        assert!(long.as_mut().now_or_never().is_none());
+
+        clock.advance(Duration::from_millis(100));
+        assert!(long.now_or_never().is_some());
+
+        let dropped = clock.delay(Duration::from_millis(100));
+        assert_eq!(clock.pending_delays(), 1);
+        drop(dropped);
+        assert_eq!(clock.pending_delays(), 0);
+    }
+}
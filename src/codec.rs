@@ -1,11 +1,13 @@
 use bytes::Bytes;
 use rlp::{Decodable, DecoderError, Encodable, Prototype, Rlp, RlpStream};
 
+use crate::smr::smr_types::{Lock, SMRStatus, Step};
 use crate::types::{
-    Address, AggregatedSignature, AggregatedVote, Commit, Feed, Hash, Node, PoLC, Proof, Proposal,
-    Signature, SignedProposal, SignedVote, Status, VerifyResp, Vote, VoteType,
+    Address, AggregatedSignature, AggregatedVote, Commit, Feed, Hash, LastSigned, Node, PoLC,
+    Proof, Proposal, Signature, SignedProposal, SignedVote, Status, VerifyResp, Vote, VoteType,
 };
-use crate::Codec;
+use crate::wal::{WalMsgType, WalRecord};
+use crate::{Codec, DurationConfig};
 
 // impl Encodable and Decodable trait for SignedProposal
 impl<T: Codec> Encodable for SignedProposal<T> {
@@ -300,25 +302,47 @@ impl Encodable for Status {
         } else {
             self.interval.clone().unwrap()
         };
-        s.begin_list(3)
-            .append(&self.epoch_id)
-            .append(&tmp)
-            .append_list(&self.authority_list);
+        // The timer config is appended only if it is some, so that the status without the timer
+        // config is encoded as before.
+        if let Some(config) = self.timer_config.as_ref() {
+            s.begin_list(6)
+                .append(&self.epoch_id)
+                .append(&tmp)
+                .append_list(&self.authority_list)
+                .append(&config.propose_ratio)
+                .append(&config.prevote_ratio)
+                .append(&config.precommit_ratio);
+        } else {
+            s.begin_list(3)
+                .append(&self.epoch_id)
+                .append(&tmp)
+                .append_list(&self.authority_list);
+        }
     }
 }
 
 impl Decodable for Status {
     fn decode(r: &Rlp) -> Result<Self, DecoderError> {
         match r.prototype()? {
-            Prototype::List(3) => {
+            Prototype::List(len) if len == 3 || len == 6 => {
                 let epoch_id: u64 = r.val_at(0)?;
                 let tmp: u64 = r.val_at(1)?;
                 let authority_list: Vec<Node> = r.list_at(2)?;
                 let interval = if tmp == 0 { None } else { Some(tmp) };
+                let timer_config = if len == 6 {
+                    Some(DurationConfig::new(
+                        r.val_at(3)?,
+                        r.val_at(4)?,
+                        r.val_at(5)?,
+                    ))
+                } else {
+                    None
+                };
 
                 Ok(Status {
                     epoch_id,
                     interval,
+                    timer_config,
                     authority_list,
                 })
             }
@@ -391,7 +415,8 @@ impl<T: Codec> Decodable for Feed<T> {
 // impl Encodable and Decodable trait for VerifyResp
 impl Encodable for VerifyResp {
     fn rlp_append(&self, s: &mut RlpStream) {
-        s.begin_list(2)
+        s.begin_list(3)
+            .append(&self.epoch_id)
             .append(&self.epoch_hash.to_vec())
             .append(&self.is_pass);
     }
@@ -400,11 +425,13 @@ impl Encodable for VerifyResp {
 impl Decodable for VerifyResp {
     fn decode(r: &Rlp) -> Result<Self, DecoderError> {
         match r.prototype()? {
-            Prototype::List(2) => {
-                let tmp: Vec<u8> = r.val_at(0)?;
+            Prototype::List(3) => {
+                let epoch_id: u64 = r.val_at(0)?;
+                let tmp: Vec<u8> = r.val_at(1)?;
                 let epoch_hash = Hash::from(tmp);
-                let is_pass: bool = r.val_at(1)?;
+                let is_pass: bool = r.val_at(2)?;
                 Ok(VerifyResp {
+                    epoch_id,
                     epoch_hash,
                     is_pass,
                 })
@@ -414,6 +441,133 @@ impl Decodable for VerifyResp {
     }
 }
 
+// impl Encodable and Decodable trait for LastSigned
+impl Encodable for LastSigned {
+    fn rlp_append(&self, s: &mut RlpStream) {
+        let step: u8 = self.step.clone().into();
+        s.begin_list(4)
+            .append(&self.epoch_id)
+            .append(&self.round)
+            .append(&step)
+            .append(&self.epoch_hash.to_vec());
+    }
+}
+
+impl Decodable for LastSigned {
+    fn decode(r: &Rlp) -> Result<Self, DecoderError> {
+        match r.prototype()? {
+            Prototype::List(4) => {
+                let epoch_id: u64 = r.val_at(0)?;
+                let round: u64 = r.val_at(1)?;
+                let tmp: u8 = r.val_at(2)?;
+                let step =
+                    Step::from_u8(tmp).ok_or_else(|| DecoderError::Custom("Invalid step."))?;
+                let tmp: Vec<u8> = r.val_at(3)?;
+                let epoch_hash = Hash::from(tmp);
+                Ok(LastSigned {
+                    epoch_id,
+                    round,
+                    step,
+                    epoch_hash,
+                })
+            }
+            _ => Err(DecoderError::RlpInconsistentLengthAndData),
+        }
+    }
+}
+
+// impl Encodable and Decodable trait for WalRecord
+impl Encodable for WalRecord {
+    fn rlp_append(&self, s: &mut RlpStream) {
+        let msg_type: u8 = self.msg_type.clone().into();
+        s.begin_list(3)
+            .append(&msg_type)
+            .append(&self.epoch_id)
+            .append(&self.msg);
+    }
+}
+
+impl Decodable for WalRecord {
+    fn decode(r: &Rlp) -> Result<Self, DecoderError> {
+        match r.prototype()? {
+            Prototype::List(3) => {
+                let tmp: u8 = r.val_at(0)?;
+                let msg_type = WalMsgType::from_u8(tmp)
+                    .ok_or_else(|| DecoderError::Custom("Invalid wal message type."))?;
+                let epoch_id: u64 = r.val_at(1)?;
+                let msg: Vec<u8> = r.val_at(2)?;
+                Ok(WalRecord {
+                    msg_type,
+                    epoch_id,
+                    msg,
+                })
+            }
+            _ => Err(DecoderError::RlpInconsistentLengthAndData),
+        }
+    }
+}
+
+// impl Encodable and Decodable trait for Lock
+impl Encodable for Lock {
+    fn rlp_append(&self, s: &mut RlpStream) {
+        s.begin_list(2)
+            .append(&self.round)
+            .append(&self.hash.to_vec());
+    }
+}
+
+impl Decodable for Lock {
+    fn decode(r: &Rlp) -> Result<Self, DecoderError> {
+        match r.prototype()? {
+            Prototype::List(2) => {
+                let round: u64 = r.val_at(0)?;
+                let tmp: Vec<u8> = r.val_at(1)?;
+                let hash = Hash::from(tmp);
+                Ok(Lock { round, hash })
+            }
+            _ => Err(DecoderError::RlpInconsistentLengthAndData),
+        }
+    }
+}
+
+// impl Encodable and Decodable trait for SMRStatus
+impl Encodable for SMRStatus {
+    fn rlp_append(&self, s: &mut RlpStream) {
+        let step: u8 = self.step.clone().into();
+        s.begin_list(5)
+            .append(&self.epoch_id)
+            .append(&self.round)
+            .append(&step)
+            .append(&self.epoch_hash.to_vec())
+            .append(&self.lock);
+    }
+}
+
+impl Decodable for SMRStatus {
+    fn decode(r: &Rlp) -> Result<Self, DecoderError> {
+        match r.prototype()? {
+            Prototype::List(5) => {
+                let epoch_id: u64 = r.val_at(0)?;
+                let round: u64 = r.val_at(1)?;
+                let tmp: u8 = r.val_at(2)?;
+                let step =
+                    Step::from_u8(tmp).ok_or_else(|| DecoderError::Custom("Invalid step."))?;
+                let tmp: Vec<u8> = r.val_at(3)?;
+                let epoch_hash = Hash::from(tmp);
+                let lock = r.val_at(4)?;
+                Ok(SMRStatus {
+                    epoch_id,
+                    round,
+                    step,
+                    epoch_hash,
+                    lock,
+                })
+            }
+            _ => Err(DecoderError::RlpInconsistentLengthAndData),
+        }
+    }
+}
+
 #[cfg(test)]
 mod test {
     use std::error::Error;
@@ -421,14 +575,16 @@ mod test {
     use bincode::{deserialize, serialize};
     use bytes::Bytes;
     use rand::random;
-    use rlp::Encodable;
+    use rlp::{DecoderError, Encodable, RlpStream};
     use serde::{Deserialize, Serialize};
 
+    use crate::smr::smr_types::{Lock, SMRStatus, Step};
     use crate::types::{
-        Address, AggregatedSignature, AggregatedVote, Commit, Feed, Hash, Node, PoLC, Proof,
-        Proposal, Signature, SignedProposal, SignedVote, Status, VerifyResp, Vote, VoteType,
+        Address, AggregatedSignature, AggregatedVote, Commit, Feed, Hash, LastSigned, Node, PoLC,
+        Proof, Proposal, Signature, SignedProposal, SignedVote, Status, VerifyResp, Vote, VoteType,
     };
-    use crate::Codec;
+    use crate::wal::{WalMsgType, WalRecord};
+    use crate::{Codec, DurationConfig};
 
     #[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
     struct Pill {
@@ -549,10 +705,11 @@ mod test {
     }
 
     impl Status {
-        fn new(time: Option<u64>) -> Self {
+        fn new(time: Option<u64>, timer_config: Option<DurationConfig>) -> Self {
             Status {
-                epoch_id:       random::<u64>(),
-                interval:       time,
+                epoch_id: random::<u64>(),
+                interval: time,
+                timer_config,
                 authority_list: vec![Node::new(gen_address())],
             }
         }
@@ -570,10 +727,54 @@ mod test {
         }
     }
 
+    impl LastSigned {
+        fn new(step: Step) -> Self {
+            LastSigned {
+                epoch_id: random::<u64>(),
+                round: random::<u64>(),
+                step,
+                epoch_hash: gen_hash(),
+            }
+        }
+    }
+
+    impl WalRecord {
+        fn new(msg_type: WalMsgType) -> Self {
+            WalRecord {
+                msg_type,
+                epoch_id: random::<u64>(),
+                msg: gen_signature().to_vec(),
+            }
+        }
+    }
+
+    impl SMRStatus {
+        fn new(step: Step, lock: bool) -> Self {
+            let epoch_hash = gen_hash();
+            let lock = if lock {
+                Some(Lock {
+                    round: random::<u64>(),
+                    hash:  epoch_hash.clone(),
+                })
+            } else {
+                None
+            };
+
+            SMRStatus {
+                epoch_id: random::<u64>(),
+                round: random::<u64>(),
+                step,
+                epoch_hash,
+                lock,
+            }
+        }
+    }
+
     impl VerifyResp {
         fn new(is_pass: bool) -> Self {
             let epoch_hash = gen_hash();
             VerifyResp {
+                epoch_id: random::<u64>(),
                 epoch_hash,
                 is_pass,
             }
@@ -643,12 +844,17 @@ mod test {
         assert_eq!(commit, res);
 
         // Test Status
-        let status = Status::new(None);
+        let status = Status::new(None, None);
+        let res: Status = rlp::decode(&status.rlp_bytes()).unwrap();
+        assert_eq!(status, res);
+
+        // Test Status
+        let status = Status::new(Some(3000), None);
         let res: Status = rlp::decode(&status.rlp_bytes()).unwrap();
         assert_eq!(status, res);
 
         // Test Status
-        let status = Status::new(Some(3000));
+        let status = Status::new(Some(3000), Some(DurationConfig::new(15, 10, 5)));
         let res: Status = rlp::decode(&status.rlp_bytes()).unwrap();
         assert_eq!(status, res);
 
@@ -665,5 +871,55 @@ mod test {
         let verify_response = VerifyResp::new(false);
         let res: VerifyResp = rlp::decode(&verify_response.rlp_bytes()).unwrap();
         assert_eq!(verify_response, res);
+
+        // Test WalRecord
+        let wal_record = WalRecord::new(WalMsgType::SignedVote);
+        let res: WalRecord = rlp::decode(&wal_record.rlp_bytes()).unwrap();
+        assert_eq!(wal_record, res);
+
+        let wal_record = WalRecord::new(WalMsgType::Commit);
+        let res: WalRecord = rlp::decode(&wal_record.rlp_bytes()).unwrap();
+        assert_eq!(wal_record, res);
+
+        // Test SMRStatus
+        let smr_status = SMRStatus::new(Step::Prevote, false);
+        let res: SMRStatus = rlp::decode(&smr_status.rlp_bytes()).unwrap();
+        assert_eq!(smr_status, res);
+
+        let smr_status = SMRStatus::new(Step::Precommit, true);
+        let res: SMRStatus = rlp::decode(&smr_status.rlp_bytes()).unwrap();
+        assert_eq!(smr_status, res);
+
+        // Test LastSigned
+        let last_signed = LastSigned::new(Step::Propose);
+        let res: LastSigned = rlp::decode(&last_signed.rlp_bytes()).unwrap();
+        assert_eq!(last_signed, res);
+
+        let last_signed = LastSigned::new(Step::Precommit);
+        let res: LastSigned = rlp::decode(&last_signed.rlp_bytes()).unwrap();
+        assert_eq!(last_signed, res);
+    }
+
+    #[test]
+    fn test_invalid_step() {
+        let lock: Option<Lock> = None;
+        let mut stream = RlpStream::new_list(5);
+        stream
+            .append(&1u64)
+            .append(&0u64)
+            .append(&9u8)
+            .append(&gen_hash().to_vec())
+            .append(&lock);
+        let res: Result<SMRStatus, _> = rlp::decode(&stream.out());
+        assert_eq!(res, Err(DecoderError::Custom("Invalid step.")));
+
+        let mut stream = RlpStream::new_list(4);
+        stream
+            .append(&1u64)
+            .append(&0u64)
+            .append(&9u8)
+            .append(&gen_hash().to_vec());
+        let res: Result<LastSigned, _> = rlp::decode(&stream.out());
+        assert_eq!(res, Err(DecoderError::Custom("Invalid step.")));
     }
 }
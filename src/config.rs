@@ -0,0 +1,338 @@
+use serde::{Deserialize, Serialize};
+
+use crate::{backoff::BackoffConfig, error::ConsensusError, ConsensusResult, DurationConfig};
+
+const DEFAULT_INTERVAL: u64 = 3000;
+const DEFAULT_FUTURE_EPOCH_GAP: u64 = 5;
+const DEFAULT_FUTURE_ROUND_GAP: u64 = 10;
+const DEFAULT_CHECK_EPOCH_TIMEOUT: u64 = 5000;
+const DEFAULT_MAX_PROPOSE_BACKOFF: u32 = 10;
+const DEFAULT_COMMIT_RETRY_TIMES: u32 = 5;
+const DEFAULT_COMMIT_RETRY_INTERVAL: u64 = 500;
+const DEFAULT_MAX_COMMIT_BACKOFF: u32 = 10;
+const DEFAULT_CALLBACK_RETRY_TIMES: u32 = 3;
+const DEFAULT_CALLBACK_RETRY_INTERVAL: u64 = 100;
+const DEFAULT_MSG_CAPACITY: usize = 1024;
+
+/// The configuration of an overlord instance. It can be built by `OverlordConfigBuilder` or
+/// deserialized, and the missing fields take the default values. The configuration is validated
+/// when the instance starts running.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+#[serde(default)]
+pub struct OverlordConfig {
+    /// The epoch interval as millisecond.
+    pub interval: u64,
+    /// The timeout ratios of each step. Use the default ratios if it is `None`.
+    pub duration: Option<DurationConfig>,
+    /// The max gap between the epoch ID of a cached future message and the current.
+    pub future_epoch_gap: u64,
+    /// The max gap between the round of a cached future message and the current.
+    pub future_round_gap: u64,
+    /// The timeout of checking an epoch as millisecond. After that, the epoch is regarded as
+    /// failed to check.
+    pub check_epoch_timeout: u64,
+    /// The backoff policy of the propose timeout. The default is doubling the timeout each round,
+    /// which is capped at `2^10` times.
+    pub propose_backoff: BackoffConfig,
+    /// The backoff policy of the prevote timeout. The default is no backoff.
+    pub prevote_backoff: BackoffConfig,
+    /// The backoff policy of the precommit timeout. The default is no backoff.
+    pub precommit_backoff: BackoffConfig,
+    /// How many times to retry a failed commit. Zero means never retry.
+    pub commit_retry_times: u32,
+    /// The interval before the first commit retry as millisecond, which is extended by the
+    /// `commit_retry_backoff` after each retry.
+    pub commit_retry_interval: u64,
+    /// The backoff policy of the commit retry interval. The default is doubling the interval after
+    /// each retry, which is capped at `2^10` times.
+    pub commit_retry_backoff: BackoffConfig,
+    /// How many times to retry a failed callback of getting an epoch or an authority list, if the
+    /// error should be retried. Zero means never retry.
+    pub callback_retry_times: u32,
+    /// The interval before the first callback retry as millisecond, which grows linearly with the
+    /// retry times.
+    pub callback_retry_interval: u64,
+    /// Set the step timeouts by the observed latency instead of the fixed ratios if it is `Some`.
+    pub adaptive_timeout: Option<AdaptiveTimeoutConfig>,
+    /// The deadline of the commit step as millisecond if it is `Some`. After the node stays in the
+    /// commit step for that long, a `CommitStalled` event is published and the application is
+    /// asked for the latest status. The commit watchdog is disabled by default.
+    pub commit_timeout: Option<u64>,
+    /// The capacity of the queue of the messages from the network, see
+    /// `OverlordHandler::send_msg`.
+    pub msg_capacity: usize,
+}
+
+impl Default for OverlordConfig {
+    fn default() -> Self {
+        OverlordConfig {
+            interval:                DEFAULT_INTERVAL,
+            duration:                None,
+            future_epoch_gap:        DEFAULT_FUTURE_EPOCH_GAP,
+            future_round_gap:        DEFAULT_FUTURE_ROUND_GAP,
+            check_epoch_timeout:     DEFAULT_CHECK_EPOCH_TIMEOUT,
+            propose_backoff:         BackoffConfig::CappedExponential(DEFAULT_MAX_PROPOSE_BACKOFF),
+            prevote_backoff:         BackoffConfig::None,
+            precommit_backoff:       BackoffConfig::None,
+            commit_retry_times:      DEFAULT_COMMIT_RETRY_TIMES,
+            commit_retry_interval:   DEFAULT_COMMIT_RETRY_INTERVAL,
+            commit_retry_backoff:    BackoffConfig::CappedExponential(DEFAULT_MAX_COMMIT_BACKOFF),
+            callback_retry_times:    DEFAULT_CALLBACK_RETRY_TIMES,
+            callback_retry_interval: DEFAULT_CALLBACK_RETRY_INTERVAL,
+            adaptive_timeout:        None,
+            commit_timeout:          None,
+            msg_capacity:            DEFAULT_MSG_CAPACITY,
+        }
+    }
+}
+
+impl OverlordConfig {
+    /// Create a builder with the default configuration.
+    pub fn builder() -> OverlordConfigBuilder {
+        OverlordConfigBuilder::default()
+    }
+
+    /// Validate the configuration. Return `Err(ConsensusError::ConfigErr)` with the reason if any
+    /// field is invalid.
+    pub fn validate(&self) -> ConsensusResult<()> {
+        if self.interval == 0 {
+            return Err(config_err("interval must be greater than 0"));
+        }
+
+        if let Some(duration) = self.duration.as_ref() {
+            if !duration.is_valid() {
+                return Err(config_err("timeout ratios must be greater than 0"));
+            }
+        }
+
+        if self.future_epoch_gap == 0 {
+            return Err(config_err("future epoch gap must be greater than 0"));
+        }
+
+        if self.future_round_gap == 0 {
+            return Err(config_err("future round gap must be greater than 0"));
+        }
+
+        if self.check_epoch_timeout == 0 {
+            return Err(config_err("check epoch timeout must be greater than 0"));
+        }
+
+        if self.commit_retry_interval == 0 {
+            return Err(config_err("commit retry interval must be greater than 0"));
+        }
+
+        if self.callback_retry_interval == 0 {
+            return Err(config_err("callback retry interval must be greater than 0"));
+        }
+
+        if self.commit_timeout == Some(0) {
+            return Err(config_err("commit timeout must be greater than 0"));
+        }
+
+        if self.msg_capacity == 0 {
+            return Err(config_err("message capacity must be greater than 0"));
+        }
+
+        if let Some(adaptive) = self.adaptive_timeout.as_ref() {
+            if adaptive.min_timeout == 0 || adaptive.min_timeout > adaptive.max_timeout {
+                return Err(ConsensusError::ConfigErr(format!(
+                    "invalid adaptive timeout bounds [{}, {}]",
+                    adaptive.min_timeout, adaptive.max_timeout
+                )));
+            }
+        }
+        Ok(())
+    }
+}
+
+/// The configuration of the adaptive step timeouts. The latency of each step is measured every
+/// round, which is the time from entering the step to receiving the proposal or the quorum
+/// certificate. The timeout of the step is set by a moving estimate of the latency and its
+/// deviation, and it is doubled on each timeout of the step until a latency is observed again. It
+/// is bounded by `[min_timeout, max_timeout]`. Before any latency is observed, the timeout is
+/// computed by the fixed ratios.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+pub struct AdaptiveTimeoutConfig {
+    /// The lower bound of each step timeout as millisecond.
+    pub min_timeout: u64,
+    /// The upper bound of each step timeout as millisecond.
+    pub max_timeout: u64,
+}
+
+impl AdaptiveTimeoutConfig {
+    /// Create a new adaptive timeout configuration with the bounds as millisecond.
+    pub fn new(min_timeout: u64, max_timeout: u64) -> Self {
+        AdaptiveTimeoutConfig {
+            min_timeout,
+            max_timeout,
+        }
+    }
+}
+
+/// A builder of the overlord configuration.
+#[derive(Clone, Debug, Default)]
+pub struct OverlordConfigBuilder {
+    config: OverlordConfig,
+}
+
+impl OverlordConfigBuilder {
+    /// Set the epoch interval as millisecond.
+    pub fn interval(mut self, interval: u64) -> Self {
+        self.config.interval = interval;
+        self
+    }
+
+    /// Set the timeout ratios of each step.
+    pub fn duration(mut self, duration: DurationConfig) -> Self {
+        self.config.duration = Some(duration);
+        self
+    }
+
+    /// Set the max gap of the epoch ID of a cached future message.
+    pub fn future_epoch_gap(mut self, gap: u64) -> Self {
+        self.config.future_epoch_gap = gap;
+        self
+    }
+
+    /// Set the max gap of the round of a cached future message.
+    pub fn future_round_gap(mut self, gap: u64) -> Self {
+        self.config.future_round_gap = gap;
+        self
+    }
+
+    /// Set the timeout of checking an epoch as millisecond.
+    pub fn check_epoch_timeout(mut self, timeout: u64) -> Self {
+        self.config.check_epoch_timeout = timeout;
+        self
+    }
+
+    /// Set the backoff policy of the propose timeout.
+    pub fn propose_backoff(mut self, backoff: BackoffConfig) -> Self {
+        self.config.propose_backoff = backoff;
+        self
+    }
+
+    /// Set the backoff policy of the prevote timeout.
+    pub fn prevote_backoff(mut self, backoff: BackoffConfig) -> Self {
+        self.config.prevote_backoff = backoff;
+        self
+    }
+
+    /// Set the backoff policy of the precommit timeout.
+    pub fn precommit_backoff(mut self, backoff: BackoffConfig) -> Self {
+        self.config.precommit_backoff = backoff;
+        self
+    }
+
+    /// Set how many times to retry a failed commit.
+    pub fn commit_retry_times(mut self, times: u32) -> Self {
+        self.config.commit_retry_times = times;
+        self
+    }
+
+    /// Set the interval before the first commit retry as millisecond.
+    pub fn commit_retry_interval(mut self, interval: u64) -> Self {
+        self.config.commit_retry_interval = interval;
+        self
+    }
+
+    /// Set the backoff policy of the commit retry interval.
+    pub fn commit_retry_backoff(mut self, backoff: BackoffConfig) -> Self {
+        self.config.commit_retry_backoff = backoff;
+        self
+    }
+
+    /// Set how many times to retry a failed callback.
+    pub fn callback_retry_times(mut self, times: u32) -> Self {
+        self.config.callback_retry_times = times;
+        self
+    }
+
+    /// Set the interval before the first callback retry as millisecond.
+    pub fn callback_retry_interval(mut self, interval: u64) -> Self {
+        self.config.callback_retry_interval = interval;
+        self
+    }
+
+    /// Enable the adaptive step timeouts.
+    pub fn adaptive_timeout(mut self, adaptive: AdaptiveTimeoutConfig) -> Self {
+        self.config.adaptive_timeout = Some(adaptive);
+        self
+    }
+
+    /// Enable the commit watchdog with the deadline of the commit step as millisecond.
+    pub fn commit_timeout(mut self, timeout: u64) -> Self {
+        self.config.commit_timeout = Some(timeout);
+        self
+    }
+
+    /// Set the capacity of the queue of the messages from the network.
+    pub fn msg_capacity(mut self, capacity: usize) -> Self {
+        self.config.msg_capacity = capacity;
+        self
+    }
+
+    /// Validate and build the configuration.
+    pub fn build(self) -> ConsensusResult<OverlordConfig> {
+        self.config.validate()?;
+        Ok(self.config)
+    }
+}
+
+fn config_err(reason: &str) -> ConsensusError {
+    ConsensusError::ConfigErr(reason.to_string())
+}
+
+#[cfg(test)]
+mod test {
+    use crate::backoff::BackoffConfig;
+    use crate::config::{AdaptiveTimeoutConfig, OverlordConfig};
+    use crate::error::ConsensusError;
+    use crate::DurationConfig;
+
+    #[test]
+    fn test_build() {
+        let config = OverlordConfig::builder()
+            .interval(1000)
+            .duration(DurationConfig::new(10, 10, 10))
+            .build()
+            .unwrap();
+        assert_eq!(config.interval, 1000);
+        assert_eq!(config.future_epoch_gap, 5);
+
+        for builder in vec![
+            OverlordConfig::builder().interval(0),
+            OverlordConfig::builder().duration(DurationConfig::new(10, 0, 10)),
+            OverlordConfig::builder().future_epoch_gap(0),
+            OverlordConfig::builder().future_round_gap(0),
+            OverlordConfig::builder().check_epoch_timeout(0),
+            OverlordConfig::builder().commit_retry_interval(0),
+            OverlordConfig::builder().callback_retry_interval(0),
+            OverlordConfig::builder().commit_timeout(0),
+            OverlordConfig::builder().msg_capacity(0),
+            OverlordConfig::builder().adaptive_timeout(AdaptiveTimeoutConfig::new(0, 100)),
+            OverlordConfig::builder().adaptive_timeout(AdaptiveTimeoutConfig::new(200, 100)),
+        ]
+        .into_iter()
+        {
+            let err = ConsensusError::ConfigErr(String::new());
+            assert_eq!(builder.build(), Err(err));
+        }
+    }
+
+    #[test]
+    fn test_deserialize() {
+        let config: OverlordConfig =
+            serde_json::from_str(r#"{"interval": 1000, "future_round_gap": 20}"#).unwrap();
+        assert_eq!(config.interval, 1000);
+        assert_eq!(config.future_round_gap, 20);
+        assert_eq!(config.check_epoch_timeout, 5000);
+        assert_eq!(config.callback_retry_times, 3);
+        assert_eq!(config.msg_capacity, 1024);
+        assert_eq!(
+            config.commit_retry_backoff,
+            BackoffConfig::CappedExponential(10)
+        );
+        assert!(config.validate().is_ok());
+    }
+}
@@ -0,0 +1,48 @@
+use creep::Context;
+
+use crate::smr::smr_types::Step;
+
+/// The key of the current epoch ID in the context, the value is `u64`.
+pub const EPOCH_ID: &str = "overlord_epoch_id";
+/// The key of the current round in the context, the value is `u64`.
+pub const ROUND: &str = "overlord_round";
+/// The key of the current step in the context, the value is `Step`.
+pub const STEP: &str = "overlord_step";
+/// The key of the message kind in the context, the value is `String`. It is the kind of the
+/// message to send for the `broadcast_to_other` and `transmit_to_relayer` callbacks, otherwise the
+/// kind of the received message that causes the callback.
+pub const MSG_KIND: &str = "overlord_msg_kind";
+
+/// Add the current epoch ID, round and step into the context.
+pub(crate) fn with_status(ctx: &Context, epoch_id: u64, round: u64, step: &Step) -> Context {
+    ctx.with_value(EPOCH_ID, epoch_id)
+        .with_value(ROUND, round)
+        .with_value(STEP, step.clone())
+}
+
+/// Add the message kind into the context.
+pub(crate) fn with_msg_kind<K: ToString>(ctx: &Context, kind: K) -> Context {
+    ctx.with_value(MSG_KIND, kind.to_string())
+}
+
+#[cfg(test)]
+mod test {
+    use creep::Context;
+
+    use crate::context::{with_msg_kind, with_status, EPOCH_ID, MSG_KIND, ROUND, STEP};
+    use crate::smr::smr_types::Step;
+
+    #[test]
+    fn test_context() {
+        let ctx = with_status(&Context::new(), 10, 2, &Step::Prevote);
+        let ctx = with_msg_kind(&ctx, "Signed Vote");
+
+        assert_eq!(ctx.get::<u64>(EPOCH_ID), Some(&10));
+        assert_eq!(ctx.get::<u64>(ROUND), Some(&2));
+        assert_eq!(ctx.get::<Step>(STEP), Some(&Step::Prevote));
+        assert_eq!(
+            ctx.get::<String>(MSG_KIND),
+            Some(&"Signed Vote".to_string())
+        );
+    }
+}
@@ -60,17 +60,122 @@ pub enum ConsensusError {
     #[display(fmt = "Wal error {}", _0)]
     WalErr(String),
     ///
+    #[display(fmt = "Wal corrupted at offset {}, {}", offset, reason)]
+    WalCorrupted {
+        ///
+        offset: u64,
+        ///
+        reason: String,
+    },
+    ///
+    #[display(fmt = "Double sign error {}", _0)]
+    DoubleSignErr(String),
+    ///
+    #[display(fmt = "Config error {}", _0)]
+    ConfigErr(String),
+    ///
+    #[display(fmt = "Message queue is full")]
+    MsgQueueFull,
+    ///
     #[display(fmt = "Crypto error {}", _0)]
     CryptoErr(String),
     ///
     #[display(fmt = "Aggregated signature error {}", _0)]
     AggregatedSignatureErr(String),
+    /// The `get_epoch` callback failed.
+    #[display(fmt = "Get epoch {} error {}", epoch_id, source)]
+    GetEpochErr {
+        /// Epoch ID of the request.
+        epoch_id: u64,
+        /// The error returned by the callback.
+        source: Box<dyn Error + Send>,
+    },
+    /// The `check_epoch` callback failed.
+    #[display(fmt = "Check epoch {} error {}", epoch_id, source)]
+    CheckEpochErr {
+        /// Epoch ID of the request.
+        epoch_id: u64,
+        /// The error returned by the callback.
+        source: Box<dyn Error + Send>,
+    },
+    /// The `commit` callback failed.
+    #[display(fmt = "Commit epoch {} error {}", epoch_id, source)]
+    CommitErr {
+        /// Epoch ID of the request.
+        epoch_id: u64,
+        /// The error returned by the callback.
+        source: Box<dyn Error + Send>,
+    },
+    /// The `get_authority_list` callback failed.
+    #[display(fmt = "Get authority list of epoch {} error {}", epoch_id, source)]
+    GetAuthorityListErr {
+        /// Epoch ID of the request.
+        epoch_id: u64,
+        /// The error returned by the callback.
+        source: Box<dyn Error + Send>,
+    },
+    /// The `get_latest_status` callback failed.
+    #[display(fmt = "Get latest status of epoch {} error {}", epoch_id, source)]
+    GetLatestStatusErr {
+        /// Epoch ID of the request.
+        epoch_id: u64,
+        /// The error returned by the callback.
+        source: Box<dyn Error + Send>,
+    },
     /// Other error.
     #[display(fmt = "Other error {}", _0)]
     Other(String),
 }
 
-impl Error for ConsensusError {}
+/// The policy of handling an error while the consensus is running.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ErrorPolicy {
+    /// The consensus can not go on, stop it and return the error.
+    Fatal,
+    /// The error may be transient, retry the failed callback.
+    Retry,
+    /// Log the error and go on.
+    Ignore,
+}
+
+impl ConsensusError {
+    /// Get the policy of handling the error. The errors of the channels, the SMR and the Wal are
+    /// fatal. The errors of the callbacks that read from the application are retried, since they
+    /// are usually transient. Other errors are caused by a single message, which are ignored.
+    pub fn policy(&self) -> ErrorPolicy {
+        use self::ConsensusError::*;
+        match self {
+            ChannelErr(_)
+            | TriggerSMRErr(_)
+            | MonitorEventErr(_)
+            | ThrowEventErr(_)
+            | StorageErr(_)
+            | WalErr(_)
+            | WalCorrupted { .. } => ErrorPolicy::Fatal,
+            GetEpochErr { .. } | GetAuthorityListErr { .. } => ErrorPolicy::Retry,
+            _ => ErrorPolicy::Ignore,
+        }
+    }
+
+    /// Whether the error is fatal.
+    pub fn is_fatal(&self) -> bool {
+        self.policy() == ErrorPolicy::Fatal
+    }
+}
+
+impl Error for ConsensusError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        use self::ConsensusError::*;
+        match self {
+            GetEpochErr { source, .. }
+            | CheckEpochErr { source, .. }
+            | CommitErr { source, .. }
+            | GetAuthorityListErr { source, .. }
+            | GetLatestStatusErr { source, .. } => Some(source.as_ref()),
+            _ => None,
+        }
+    }
+}
 
 #[cfg(test)]
 impl PartialEq for ConsensusError {
@@ -80,16 +185,27 @@ impl PartialEq for ConsensusError {
             // If compare objects are the following types of error, as long as the error type need
             // the same, the details are ignored.
             (InvalidAddress, InvalidAddress)
+            | (ConfigErr(_), ConfigErr(_))
+            | (MsgQueueFull, MsgQueueFull)
             | (TriggerSMRErr(_), TriggerSMRErr(_))
             | (MonitorEventErr(_), MonitorEventErr(_))
             | (ThrowEventErr(_), ThrowEventErr(_))
             | (ProposalErr(_), ProposalErr(_))
             | (PrevoteErr(_), PrevoteErr(_))
             | (PrecommitErr(_), PrecommitErr(_))
-            | (SelfCheckErr(_), SelfCheckErr(_)) => true,
-            // If it is the following two types of errors, in the judgment, the error type need the
+            | (SelfCheckErr(_), SelfCheckErr(_))
+            | (DoubleSignErr(_), DoubleSignErr(_)) => true,
+            // If it is the following types of errors, in the judgment, the error type need the
             // same, and the error information need the same.
             (RoundDiff { local: m, vote: n }, RoundDiff { local: p, vote: q }) => m == p && n == q,
+            (WalCorrupted { offset: m, .. }, WalCorrupted { offset: n, .. }) => m == n,
+            (GetEpochErr { epoch_id: m, .. }, GetEpochErr { epoch_id: n, .. })
+            | (CheckEpochErr { epoch_id: m, .. }, CheckEpochErr { epoch_id: n, .. })
+            | (CommitErr { epoch_id: m, .. }, CommitErr { epoch_id: n, .. })
+            | (GetAuthorityListErr { epoch_id: m, .. }, GetAuthorityListErr { epoch_id: n, .. })
+            | (GetLatestStatusErr { epoch_id: m, .. }, GetLatestStatusErr { epoch_id: n, .. }) => {
+                m == n
+            }
             (Other(x), Other(y)) | (CorrectnessErr(x), CorrectnessErr(y)) => x == y,
             _ => false,
         }
@@ -6,14 +6,24 @@
 #![deny(missing_docs)]
 #![feature(test)]
 
+/// Backoff policies of the step timeouts.
+pub mod backoff;
+/// Clocks of the consensus timing.
+pub mod clock;
 /// A module that impl rlp encodable and decodable trait for types that need to save wal.
 mod codec;
+/// The configuration of the overlord consensus.
+pub mod config;
+/// The keys of the consensus information that overlord adds into the context of the callbacks.
+pub mod context;
 /// Overlord error module.
 pub mod error;
 /// Create and run the overlord consensus process.
 pub mod overlord;
 /// State machine replicas module to do state changes.
 mod smr;
+/// Spawner adapters for the common executors.
+pub mod spawner;
 /// The state module to storage proposals and votes.
 mod state;
 /// The timer module to ensure the protocol liveness.
@@ -23,17 +33,21 @@ pub mod types;
 /// Some utility functions.
 mod utils;
 /// Write ahead log module.
-mod wal;
+pub mod wal;
 
+pub use self::config::{AdaptiveTimeoutConfig, OverlordConfig, OverlordConfigBuilder};
 pub use self::overlord::Overlord;
 pub use self::overlord::OverlordHandler;
+pub use self::smr::smr_types::{Lock, SMRStatus, Step};
 pub use creep::Context;
 
 use std::error::Error;
 use std::fmt::Debug;
+use std::time::{Duration, Instant};
 
 use async_trait::async_trait;
 use bytes::Bytes;
+use futures::future::BoxFuture;
 use serde::{Deserialize, Serialize};
 
 use crate::error::ConsensusError;
@@ -96,6 +110,17 @@ pub trait Consensus<T: Codec, S: Codec>: Send + Sync {
         addr: Address,
         msg: OverlordMsg<T>,
     ) -> Result<(), Box<dyn Error + Send>>;
+
+    /// Get the latest rich status of the application when the commit step of the given epoch
+    /// stalls, so that the consensus can resume from it. Return `Ok(None)` if there is no newer
+    /// status, which is the default.
+    async fn get_latest_status(
+        &self,
+        _ctx: Context,
+        _epoch_id: u64,
+    ) -> Result<Option<Status>, Box<dyn Error + Send>> {
+        Ok(None)
+    }
 }
 
 /// Trait for doing serialize and deserialize.
@@ -136,8 +161,41 @@ pub trait Crypto: Send {
     ) -> Result<(), Box<dyn Error + Send>>;
 }
 
+/// Trait for the storage of the write ahead log. Each record is appended with the epoch ID when it
+/// is saved, so that the outdated records can be flushed as going to a new epoch.
+#[async_trait]
+pub trait WalStorage: Send + Sync {
+    /// Append a record of the given epoch ID. The record must be persisted before return.
+    async fn append(&self, epoch_id: u64, record: Bytes) -> Result<(), Box<dyn Error + Send>>;
+
+    /// Load all records in the order of appending.
+    async fn load(&self) -> Result<Vec<Bytes>, Box<dyn Error + Send>>;
+
+    /// Remove the records that epoch ID is lower than `till`.
+    async fn flush(&self, till: u64) -> Result<(), Box<dyn Error + Send>>;
+}
+
+/// Trait for spawning the consensus tasks, such as the SMR, the timer and the epoch checking
+/// tasks, on the executor that the node owns. See the `spawner` module for the adapters of tokio
+/// and `futures` executors.
+pub trait Spawner: Debug + Send + Sync {
+    /// Spawn a future to run in the background.
+    fn spawn(&self, future: BoxFuture<'static, ()>);
+}
+
+/// Trait for reading the time and waiting, which is used by all the consensus timing, such as the
+/// step timeouts and the epoch interval. See the `clock` module for the system clock and a virtual
+/// clock that is advanced manually in tests.
+pub trait Clock: Debug + Send + Sync {
+    /// Get the current instant.
+    fn now(&self) -> Instant;
+
+    /// Get a future that completes after the given duration.
+    fn delay(&self, duration: Duration) -> BoxFuture<'static, ()>;
+}
+
 /// The setting of the timeout interval of each step.
-#[derive(Serialize, Deserialize, Clone, Debug)]
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
 pub struct DurationConfig {
     /// The proportion of propose timeout to the epoch interval.
     pub propose_ratio: u64,
@@ -157,6 +215,11 @@ impl DurationConfig {
         }
     }
 
+    /// Whether all the timeout ratios are greater than 0.
+    pub(crate) fn is_valid(&self) -> bool {
+        self.propose_ratio > 0 && self.prevote_ratio > 0 && self.precommit_ratio > 0
+    }
+
     pub(crate) fn get_propose_config(&self) -> (u64, u64) {
         (self.propose_ratio, 10u64)
     }
@@ -1,118 +1,423 @@
+use std::collections::BTreeMap;
+use std::fmt::{self, Debug, Formatter};
 use std::marker::PhantomData;
+use std::sync::atomic::{AtomicUsize, Ordering};
 use std::sync::Arc;
 
 use creep::Context;
-use futures::channel::mpsc::{unbounded, UnboundedReceiver, UnboundedSender};
-use parking_lot::RwLock;
+use futures::channel::mpsc::{channel, unbounded, Receiver, Sender};
+use futures::channel::mpsc::{UnboundedReceiver, UnboundedSender};
+use futures::{lock::Mutex, SinkExt};
+use parking_lot::{Mutex as SyncMutex, RwLock};
 
+use crate::backoff::BackoffPolicy;
+use crate::clock::SystemClock;
 use crate::error::ConsensusError;
-use crate::state::process::State;
-use crate::types::{Address, OverlordMsg};
-use crate::DurationConfig;
-use crate::{smr::SMR, timer::Timer};
-use crate::{Codec, Consensus, ConsensusResult, Crypto};
+use crate::smr::{smr_types::Step, Event, SMR};
+use crate::state::process::{State, StatusSnapshot};
+use crate::timer::Timer;
+use crate::types::{Address, Checkpoint, ConsensusEvent, ConsensusStatus, OverlordMsg};
+use crate::utils::{event_hub::EventHub, timer_config::TimerConfig};
+use crate::{wal::Wal, OverlordConfig};
+use crate::{Clock, Codec, Consensus, ConsensusResult, Crypto, Spawner, WalStorage};
 
 type Pile<T> = RwLock<Option<T>>;
+type Msg<T> = (Context, OverlordMsg<T>);
 
-/// An overlord consensus instance.
-pub struct Overlord<T: Codec, S: Codec, F: Consensus<T, S>, C: Crypto> {
-    sender:    Pile<UnboundedSender<(Context, OverlordMsg<T>)>>,
-    state_rx:  Pile<UnboundedReceiver<(Context, OverlordMsg<T>)>>,
-    address:   Pile<Address>,
-    consensus: Pile<Arc<F>>,
-    crypto:    Pile<C>,
-    pin_txs:   PhantomData<S>,
+/// The receivers of an overlord instance, which are taken by the state while running.
+struct Receivers<T: Codec> {
+    msg_rx:  Receiver<Msg<T>>,
+    ctrl_rx: UnboundedReceiver<Msg<T>>,
 }
 
-impl<T, S, F, C> Overlord<T, S, F, C>
+impl<T: Codec> Receivers<T> {
+    /// Replace the message queue with the one of the new run, and drop the messages that are left
+    /// over from the last run or sent while the instance is not running, so that a stale stop
+    /// message does not stop the new run at once.
+    fn reset(&mut self, msg_rx: Receiver<Msg<T>>) {
+        self.msg_rx = msg_rx;
+        while let Ok(Some(_)) = self.ctrl_rx.try_next() {}
+    }
+}
+
+/// The senders of the queue of the messages from the network. The senders waiting for the room of
+/// the queue take turns to lock the async sender, while `try_send_msg` only locks the sync sender
+/// for a moment, so that it is not blocked by a waiting sender. As each sender of a channel has
+/// its own slot, the queue holds at most two more messages than the capacity.
+struct MsgSenders<T: Codec> {
+    async_tx: Mutex<Sender<Msg<T>>>,
+    sync_tx:  SyncMutex<Sender<Msg<T>>>,
+}
+
+impl<T: Codec> MsgSenders<T> {
+    fn new(capacity: usize) -> (Arc<Self>, Receiver<Msg<T>>) {
+        let (tx, rx) = channel(capacity);
+        let senders = MsgSenders {
+            async_tx: Mutex::new(tx.clone()),
+            sync_tx:  SyncMutex::new(tx),
+        };
+        (Arc::new(senders), rx)
+    }
+}
+
+/// An overlord consensus instance. The instance can run again after the `run` function returns,
+/// no matter it is stopped or failed.
+pub struct Overlord<T: Codec, S: Codec, F: Consensus<T, S>, C: Crypto, W: WalStorage> {
+    msg_tx:      Arc<RwLock<Arc<MsgSenders<T>>>>,
+    ctrl_tx:     UnboundedSender<Msg<T>>,
+    status:      Arc<RwLock<StatusSnapshot>>,
+    receivers:   Pile<Receivers<T>>,
+    address:     Pile<Address>,
+    consensus:   Pile<Arc<F>>,
+    crypto:      Pile<C>,
+    wal:         Arc<W>,
+    event_hub:   EventHub,
+    spawner:     Arc<dyn Spawner>,
+    backoff:     RwLock<BTreeMap<Step, Arc<dyn BackoffPolicy>>>,
+    clock:       RwLock<Arc<dyn Clock>>,
+    live_timers: Arc<AtomicUsize>,
+    pin_txs:     PhantomData<S>,
+}
+
+impl<T, S, F, C, W> Overlord<T, S, F, C, W>
 where
     T: Codec + Send + Sync + 'static,
     S: Codec + Send + Sync + 'static,
     F: Consensus<T, S> + 'static,
     C: Crypto + Send + Sync + 'static,
+    W: WalStorage + 'static,
 {
-    /// Create a new overlord and return an overlord instance with an unbounded receiver.
-    pub fn new(address: Address, consensus: Arc<F>, crypto: C) -> Self {
-        let (tx, rx) = unbounded();
+    /// Create a new overlord instance. The `wal` is the storage to save the write ahead log. The
+    /// `spawner` is used to spawn the consensus tasks on the executor that the node owns.
+    pub fn new(
+        address: Address,
+        consensus: Arc<F>,
+        crypto: C,
+        wal: Arc<W>,
+        spawner: Arc<dyn Spawner>,
+    ) -> Self {
+        // The message queue is created again with the configured capacity on each run.
+        let (msg_tx, msg_rx) = MsgSenders::new(OverlordConfig::default().msg_capacity);
+        let (ctrl_tx, ctrl_rx) = unbounded();
+        let receivers = Receivers { msg_rx, ctrl_rx };
+
         Overlord {
-            sender:    RwLock::new(Some(tx)),
-            state_rx:  RwLock::new(Some(rx)),
-            address:   RwLock::new(Some(address)),
+            msg_tx: Arc::new(RwLock::new(msg_tx)),
+            ctrl_tx,
+            status: Arc::new(RwLock::new(StatusSnapshot::default())),
+            receivers: RwLock::new(Some(receivers)),
+            address: RwLock::new(Some(address)),
             consensus: RwLock::new(Some(consensus)),
-            crypto:    RwLock::new(Some(crypto)),
-            pin_txs:   PhantomData,
+            crypto: RwLock::new(Some(crypto)),
+            wal,
+            event_hub: EventHub::new(),
+            spawner,
+            backoff: RwLock::new(BTreeMap::new()),
+            clock: RwLock::new(Arc::new(SystemClock)),
+            live_timers: Arc::new(AtomicUsize::new(0)),
+            pin_txs: PhantomData,
         }
     }
 
     /// Get the overlord handler from the overlord instance.
     pub fn get_handler(&self) -> OverlordHandler<T> {
-        let sender = self.sender.write();
-        assert!(sender.is_some());
-        let tx = sender.clone().unwrap();
-        OverlordHandler::new(tx)
+        OverlordHandler::new(
+            Arc::clone(&self.msg_tx),
+            self.ctrl_tx.clone(),
+            Arc::clone(&self.status),
+            self.event_hub.clone(),
+            Arc::clone(&self.live_timers),
+        )
     }
 
-    /// Run overlord consensus process. The `interval` is the epoch interval as millisecond.
-    pub async fn run(
+    /// Set a custom backoff policy of the step timeout, which overrides the one in the
+    /// configuration. It takes effect from the next run.
+    pub fn set_backoff_policy(&self, step: Step, policy: Arc<dyn BackoffPolicy>) {
+        self.backoff.write().insert(step, policy);
+    }
+
+    /// Set the clock of the consensus timing, which is the system clock by default. A virtual clock
+    /// drives the timeouts deterministically in tests. It takes effect from the next run.
+    pub fn set_clock(&self, clock: Arc<dyn Clock>) {
+        *self.clock.write() = clock;
+    }
+
+    /// Run overlord consensus process with the given configuration. This returns `Ok(())` after
+    /// the instance is stopped by `OverlordHandler::stop`. After return, the instance can run
+    /// again, which recovers from the Wal. The messages sent while the instance is not running are
+    /// dropped. Return `Err()` if the configuration is invalid or the instance is running.
+    pub async fn run(&self, config: OverlordConfig) -> ConsensusResult<()> {
+        self.start(config, None).await
+    }
+
+    /// Run overlord consensus process from a trusted checkpoint, such as a snapshot that the node
+    /// restored. If the Wal has recorded an epoch that is not lower than the checkpoint, the
+    /// instance recovers from the Wal as `run` does. Otherwise, it enters the checkpoint epoch
+    /// directly with the authority lists of the checkpoint. Return `Err()` as `run` does, or if the
+    /// checkpoint is inconsistent.
+    pub async fn run_with_checkpoint(
         &self,
-        interval: u64,
-        timer_config: Option<DurationConfig>,
+        config: OverlordConfig,
+        checkpoint: Checkpoint,
     ) -> ConsensusResult<()> {
+        self.start(config, Some(checkpoint)).await
+    }
+
+    async fn start(
+        &self,
+        config: OverlordConfig,
+        checkpoint: Option<Checkpoint>,
+    ) -> ConsensusResult<()> {
+        config.validate()?;
+        if let Some(checkpoint) = checkpoint.as_ref() {
+            checkpoint.validate()?;
+        }
+
         let (mut smr_provider, evt_1, evt_2) = SMR::new();
-        let smr_handler = smr_provider.take_smr();
-        let timer = Timer::new(evt_2, smr_handler.clone(), interval, timer_config);
+        let mut smr_handler = smr_provider.take_smr();
+        let mut timer_config = TimerConfig::new(&config);
+        for (step, policy) in self.backoff.read().iter() {
+            timer_config.set_backoff(step, Arc::clone(policy));
+        }
+
+        let clock = Arc::clone(&*self.clock.read());
+        let timer_config = Arc::new(RwLock::new(timer_config));
+        let timer = Timer::new(
+            evt_2,
+            smr_handler.clone(),
+            Arc::clone(&timer_config),
+            self.event_hub.clone(),
+            Arc::clone(&self.spawner),
+            Arc::clone(&clock),
+            Arc::clone(&self.live_timers),
+        );
 
-        let (rx, mut state) = {
-            let mut state_rx = self.state_rx.write();
+        let (mut receivers, mut state) = {
+            let mut receivers = self.receivers.write();
             let mut address = self.address.write();
             let mut consensus = self.consensus.write();
             let mut crypto = self.crypto.write();
-            // let sender = self.sender.read();
 
-            let tmp_rx = state_rx.take().unwrap();
+            if receivers.is_none() {
+                return Err(ConsensusError::Other("Overlord is running".to_string()));
+            }
+
+            let mut tmp_receivers = receivers.take().unwrap();
+            let (msg_tx, msg_rx) = MsgSenders::new(config.msg_capacity);
+            *self.msg_tx.write() = msg_tx;
+            tmp_receivers.reset(msg_rx);
             let tmp_state = State::new(
-                smr_handler,
+                smr_handler.clone(),
                 address.take().unwrap(),
-                interval,
+                &config,
+                timer_config,
+                Arc::clone(&self.status),
                 consensus.take().unwrap(),
                 crypto.take().unwrap(),
+                Wal::new(Arc::clone(&self.wal)),
+                self.event_hub.clone(),
+                Arc::clone(&self.spawner),
+                clock,
             );
 
-            // assert!(sender.is_none());
             assert!(address.is_none());
             assert!(consensus.is_none());
             assert!(crypto.is_none());
-            assert!(state_rx.is_none());
+            assert!(receivers.is_none());
 
-            (tmp_rx, tmp_state)
+            (tmp_receivers, tmp_state)
         };
 
-        // Run SMR.
-        smr_provider.run();
-
-        // Run timer.
-        timer.run();
+        let res = run_state(
+            &mut state,
+            &mut receivers,
+            smr_provider,
+            timer,
+            evt_1,
+            self.spawner.as_ref(),
+            checkpoint,
+        )
+        .await;
+        if res.is_err() {
+            // Stop the SMR and timer if the state failed.
+            let _ = smr_handler.stop();
+        }
 
-        // Run state.
-        state.run(rx, evt_1).await?;
+        // Put back the components, so that the instance can run again.
+        let (address, consensus, crypto) = state.into_parts();
+        *self.receivers.write() = Some(receivers);
+        *self.address.write() = Some(address);
+        *self.consensus.write() = Some(consensus);
+        *self.crypto.write() = Some(crypto);
+        res
+    }
+}
 
-        Ok(())
+async fn run_state<T, S, F, C, W>(
+    state: &mut State<T, S, F, C, W>,
+    receivers: &mut Receivers<T>,
+    mut smr_provider: SMR,
+    timer: Timer,
+    event: Event,
+    spawner: &dyn Spawner,
+    checkpoint: Option<Checkpoint>,
+) -> ConsensusResult<()>
+where
+    T: Codec + Send + Sync + 'static,
+    S: Codec + Send + Sync + 'static,
+    F: Consensus<T, S> + 'static,
+    C: Crypto + Send + Sync + 'static,
+    W: WalStorage + 'static,
+{
+    // Recover state and SMR from the Wal, unless the checkpoint is higher than the Wal.
+    let checkpoint_epoch = checkpoint.as_ref().map(|checkpoint| checkpoint.epoch_id);
+    let status = state.recover(checkpoint_epoch).await?;
+    match (status, checkpoint) {
+        (Some(status), _) => smr_provider.recover(status)?,
+        (None, Some(checkpoint)) => {
+            start_from_checkpoint(state, &mut smr_provider, checkpoint).await?;
+        }
+        (None, None) => (),
     }
+
+    // Run SMR.
+    smr_provider.run(spawner);
+
+    // Run timer.
+    timer.run();
+
+    // Run state.
+    state
+        .run(&mut receivers.msg_rx, &mut receivers.ctrl_rx, event)
+        .await
+}
+
+async fn start_from_checkpoint<T, S, F, C, W>(
+    state: &mut State<T, S, F, C, W>,
+    smr_provider: &mut SMR,
+    checkpoint: Checkpoint,
+) -> ConsensusResult<()>
+where
+    T: Codec + Send + Sync + 'static,
+    S: Codec + Send + Sync + 'static,
+    F: Consensus<T, S> + 'static,
+    C: Crypto + Send + Sync + 'static,
+    W: WalStorage + 'static,
+{
+    let epoch_id = checkpoint.epoch_id;
+    state.start_from_checkpoint(checkpoint).await?;
+    smr_provider.start(epoch_id)
 }
 
-/// An overlord handler to send messages to an overlord instance.
-#[derive(Clone, Debug)]
-pub struct OverlordHandler<T: Codec>(UnboundedSender<(Context, OverlordMsg<T>)>);
+/// An overlord handler to send messages to an overlord instance, query its status and subscribe
+/// its events. The messages from the network are sent to a bounded queue, while the rich status
+/// and stop messages are sent to a separate queue which is handled in priority.
+#[derive(Clone)]
+pub struct OverlordHandler<T: Codec> {
+    msg_tx:      Arc<RwLock<Arc<MsgSenders<T>>>>,
+    ctrl_tx:     UnboundedSender<Msg<T>>,
+    status:      Arc<RwLock<StatusSnapshot>>,
+    event_hub:   EventHub,
+    live_timers: Arc<AtomicUsize>,
+}
+
+impl<T: Codec> Debug for OverlordHandler<T> {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        f.debug_struct("OverlordHandler")
+            .field("ctrl_tx", &self.ctrl_tx)
+            .field("status", &self.status)
+            .field("event_hub", &self.event_hub)
+            .field("live_timers", &self.live_timers)
+            .finish()
+    }
+}
 
 impl<T: Codec> OverlordHandler<T> {
-    fn new(tx: UnboundedSender<(Context, OverlordMsg<T>)>) -> Self {
-        OverlordHandler(tx)
+    fn new(
+        msg_tx: Arc<RwLock<Arc<MsgSenders<T>>>>,
+        ctrl_tx: UnboundedSender<Msg<T>>,
+        status: Arc<RwLock<StatusSnapshot>>,
+        event_hub: EventHub,
+        live_timers: Arc<AtomicUsize>,
+    ) -> Self {
+        OverlordHandler {
+            msg_tx,
+            ctrl_tx,
+            status,
+            event_hub,
+            live_timers,
+        }
+    }
+
+    /// Send overlord message to the instance. If the message queue is full, wait until there is
+    /// room for the message. The capacity of the queue is set by `OverlordConfig::msg_capacity`.
+    /// The rich status and stop messages never wait. Return `Err()` when the message channel is
+    /// closed, such as the instance runs again while waiting.
+    pub async fn send_msg(&self, ctx: Context, msg: OverlordMsg<T>) -> ConsensusResult<()> {
+        if is_ctrl_msg(&msg) {
+            return self.send_ctrl_msg(ctx, msg);
+        }
+
+        let senders = Arc::clone(&*self.msg_tx.read());
+        let mut msg_tx = senders.async_tx.lock().await;
+        let res = msg_tx.send((ctx, msg)).await;
+        res.map_err(|e| ConsensusError::Other(format!("Send message error {:?}", e)))
+    }
+
+    /// Try to send overlord message to the instance without waiting. Return
+    /// `Err(ConsensusError::MsgQueueFull)` if the message queue is full.
+    pub fn try_send_msg(&self, ctx: Context, msg: OverlordMsg<T>) -> ConsensusResult<()> {
+        if is_ctrl_msg(&msg) {
+            return self.send_ctrl_msg(ctx, msg);
+        }
+
+        let senders = Arc::clone(&*self.msg_tx.read());
+        let mut msg_tx = senders.sync_tx.lock();
+        msg_tx.try_send((ctx, msg)).map_err(|e| {
+            if e.is_full() {
+                ConsensusError::MsgQueueFull
+            } else {
+                ConsensusError::Other(format!("Send message error {:?}", e))
+            }
+        })
+    }
+
+    /// Stop the overlord instance gracefully. The SMR, timer and state processes will be stopped,
+    /// and the `run` function of the instance returns `Ok(())`. It takes no effect if the instance
+    /// is not running.
+    pub fn stop(&self) -> ConsensusResult<()> {
+        self.send_ctrl_msg(Context::new(), OverlordMsg::Stop)
     }
 
-    /// Send overlord message to the instance. Return `Err()` when the message channel is closed.
-    pub fn send_msg(&self, ctx: Context, msg: OverlordMsg<T>) -> ConsensusResult<()> {
-        self.0
+    /// Query a snapshot of the consensus status, which is read directly without waiting for the
+    /// instance. If the instance has not run yet, return the initial status.
+    pub fn query_status(&self) -> ConsensusStatus {
+        self.status.read().get()
+    }
+
+    /// Subscribe the consensus lifecycle events of the instance. The subscription lives across
+    /// runs of the instance. Drop the receiver to unsubscribe. The events are queued in a bounded
+    /// queue, and they are dropped while the queue is full.
+    pub fn subscribe(&self) -> Receiver<ConsensusEvent> {
+        self.event_hub.subscribe()
+    }
+
+    /// Get the number of the live step timeouts of the instance, which is at most one for each
+    /// step since the timeouts of the previous steps are cancelled as the step advances.
+    pub fn live_timers(&self) -> usize {
+        self.live_timers.load(Ordering::Acquire)
+    }
+
+    fn send_ctrl_msg(&self, ctx: Context, msg: OverlordMsg<T>) -> ConsensusResult<()> {
+        self.ctrl_tx
             .unbounded_send((ctx, msg))
             .map_err(|e| ConsensusError::Other(format!("Send message error {:?}", e)))
     }
 }
+
+fn is_ctrl_msg<T: Codec>(msg: &OverlordMsg<T>) -> bool {
+    match msg {
+        OverlordMsg::RichStatus(_) | OverlordMsg::Stop => true,
+        _ => false,
+    }
+}
@@ -7,16 +7,17 @@ mod state_machine;
 mod tests;
 
 use std::pin::Pin;
-use std::task::{Context, Poll};
+use std::task::{Context as TaskContext, Poll};
 
+use creep::Context;
 use futures::channel::mpsc::{unbounded, UnboundedReceiver, UnboundedSender};
 use futures::stream::{FusedStream, Stream, StreamExt};
-use log::error;
+use log::{error, info};
 
-use crate::smr::smr_types::{SMREvent, SMRTrigger, TriggerSource, TriggerType};
+use crate::smr::smr_types::{SMREvent, SMRStatus, SMRTrigger, TriggerSource, TriggerType};
 use crate::smr::state_machine::StateMachine;
 use crate::types::Hash;
-use crate::{error::ConsensusError, ConsensusResult};
+use crate::{error::ConsensusError, ConsensusResult, Spawner, INIT_EPOCH_ID};
 
 ///
 #[derive(Debug)]
@@ -45,64 +46,95 @@ impl SMR {
         self.smr_handler.take().unwrap()
     }
 
-    /// Run SMR module in runtime environment.
-    pub fn run(mut self) {
-        runtime::spawn(async move {
+    /// Recover the state machine from the given status, this must be called before run.
+    pub fn recover(&mut self, status: SMRStatus) -> ConsensusResult<()> {
+        self.state_machine.recover(status)
+    }
+
+    /// Start the state machine at the epoch of a trusted checkpoint, this must be called before
+    /// run.
+    pub fn start(&mut self, epoch_id: u64) -> ConsensusResult<()> {
+        self.state_machine.start(epoch_id)
+    }
+
+    /// Run SMR module on the given spawner. The SMR stops when the trigger channel is closed by a
+    /// stop trigger or all the SMR handlers are dropped.
+    pub fn run(mut self, spawner: &dyn Spawner) {
+        spawner.spawn(Box::pin(async move {
             loop {
                 let res = self.state_machine.next().await;
+                if self.state_machine.is_terminated() {
+                    info!("Overlord: SMR stopped");
+                    break;
+                }
+
                 if let Some(err) = res {
                     error!("Overlord: SMR error {:?}", err);
                 }
             }
-        });
+        }));
     }
 }
 
-///
+/// A handler to trigger the SMR. Each trigger carries the context of the message that causes it,
+/// and the SMR event thrown by the trigger carries the same context back.
 #[derive(Clone, Debug)]
 pub struct SMRHandler {
-    tx: UnboundedSender<SMRTrigger>,
+    tx: UnboundedSender<(Context, SMRTrigger)>,
 }
 
 impl SMRHandler {
     /// Create a new SMR.
-    pub fn new(sender: UnboundedSender<SMRTrigger>) -> Self {
+    pub fn new(sender: UnboundedSender<(Context, SMRTrigger)>) -> Self {
         SMRHandler { tx: sender }
     }
 
     /// A function to touch off SMR trigger gate.
-    pub fn trigger(&mut self, gate: SMRTrigger) -> ConsensusResult<()> {
+    pub fn trigger(&mut self, ctx: Context, gate: SMRTrigger) -> ConsensusResult<()> {
         let trigger_type = gate.trigger_type.clone().to_string();
         self.tx
-            .unbounded_send(gate)
+            .unbounded_send((ctx, gate))
             .map_err(|_| ConsensusError::TriggerSMRErr(trigger_type))
     }
 
     /// Trigger SMR to goto a new epoch.
-    pub fn new_epoch(&mut self, epoch_id: u64) -> ConsensusResult<()> {
+    pub fn new_epoch(&mut self, ctx: Context, epoch_id: u64) -> ConsensusResult<()> {
         let trigger = TriggerType::NewEpoch(epoch_id);
         self.tx
-            .unbounded_send(SMRTrigger {
+            .unbounded_send((ctx, SMRTrigger {
                 trigger_type: trigger.clone(),
                 source: TriggerSource::State,
                 hash: Hash::new(),
                 round: None,
                 epoch_id,
-            })
+            }))
             .map_err(|_| ConsensusError::TriggerSMRErr(trigger.to_string()))
     }
+
+    /// Trigger SMR to stop.
+    pub fn stop(&mut self) -> ConsensusResult<()> {
+        self.tx
+            .unbounded_send((Context::new(), SMRTrigger {
+                trigger_type: TriggerType::Stop,
+                source:       TriggerSource::State,
+                hash:         Hash::new(),
+                round:        None,
+                epoch_id:     INIT_EPOCH_ID,
+            }))
+            .map_err(|_| ConsensusError::TriggerSMRErr(TriggerType::Stop.to_string()))
+    }
 }
 
-///
+/// A stream of SMR events with the context of the trigger that throws the event.
 #[derive(Debug)]
 pub struct Event {
-    rx: UnboundedReceiver<SMREvent>,
+    rx: UnboundedReceiver<(Context, SMREvent)>,
 }
 
 impl Stream for Event {
-    type Item = SMREvent;
+    type Item = (Context, SMREvent);
 
-    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context) -> Poll<Option<Self::Item>> {
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut TaskContext) -> Poll<Option<Self::Item>> {
         self.rx.poll_next_unpin(cx)
     }
 }
@@ -114,7 +146,7 @@ impl FusedStream for Event {
 }
 
 impl Event {
-    pub fn new(receiver: UnboundedReceiver<SMREvent>) -> Self {
+    pub fn new(receiver: UnboundedReceiver<(Context, SMREvent)>) -> Self {
         Event { rx: receiver }
     }
 }
@@ -52,6 +52,30 @@ impl Default for Step {
     }
 }
 
+impl Into<u8> for Step {
+    fn into(self) -> u8 {
+        match self {
+            Step::Propose => 0,
+            Step::Prevote => 1,
+            Step::Precommit => 2,
+            Step::Commit => 3,
+        }
+    }
+}
+
+impl Step {
+    /// Convert a byte into a step. Return `None` if the byte is invalid.
+    pub(crate) fn from_u8(s: u8) -> Option<Self> {
+        match s {
+            0 => Some(Step::Propose),
+            1 => Some(Step::Prevote),
+            2 => Some(Step::Precommit),
+            3 => Some(Step::Commit),
+            _ => None,
+        }
+    }
+}
+
 /// SMR event that state and timer monitor this.
 /// **NOTICE**: The `epoch_id` field is just for the timer. Timer will take this to signal the timer
 /// epoch ID. State will ignore this field on handling event.
@@ -70,20 +94,26 @@ pub enum SMREvent {
     /// Prevote event,
     /// for state: transmit a prevote vote,
     /// for timer: set a prevote step timer.
+    /// The `lock_round` is the SMR lock round after handling the proposal, if the lock is some,
+    /// the lock hash is the `epoch_hash`.
     #[display(fmt = "Prevote event")]
     PrevoteVote {
         epoch_id:   u64,
         round:      u64,
         epoch_hash: Hash,
+        lock_round: Option<u64>,
     },
     /// Precommit event,
     /// for state: transmit a precommit vote,
     /// for timer: set a precommit step timer.
+    /// The `lock_round` is the SMR lock round after handling the prevote QC, if the lock is some,
+    /// the lock hash is the `epoch_hash`.
     #[display(fmt = "Precommit event")]
     PrecommitVote {
         epoch_id:   u64,
         round:      u64,
         epoch_hash: Hash,
+        lock_round: Option<u64>,
     },
     /// Commit event,
     /// for state: do commit,
@@ -112,6 +142,9 @@ pub enum TriggerType {
     /// New Epoch trigger.
     #[display(fmt = "New epoch {}", _0)]
     NewEpoch(u64),
+    /// Stop trigger.
+    #[display(fmt = "Stop")]
+    Stop,
 }
 
 /// SMR trigger sources.
@@ -126,13 +159,14 @@ pub enum TriggerSource {
 }
 
 impl Into<u8> for TriggerType {
-    /// It should not occur that call `TriggerType::NewEpoch(*).into()`.
+    /// It should not occur that call `TriggerType::NewEpoch(*).into()` or
+    /// `TriggerType::Stop.into()`.
     fn into(self) -> u8 {
         match self {
             TriggerType::Proposal => 0u8,
             TriggerType::PrevoteQC => 1u8,
             TriggerType::PrecommitQC => 2u8,
-            TriggerType::NewEpoch(_) => unreachable!(),
+            TriggerType::NewEpoch(_) | TriggerType::Stop => unreachable!(),
         }
     }
 }
@@ -158,7 +192,7 @@ impl From<u8> for TriggerType {
 /// While trigger type is `PrevoteQC` or `PrecommitQC`:
 ///     * `hash`: QC epoch hash,
 ///     * `round`: QC round, this must be `Some`.
-/// While trigger type is `NewEpoch`:
+/// While trigger type is `NewEpoch` or `Stop`:
 ///     * `hash`: A empty hash,
 ///     * `round`: This must be `None`.
 /// For each sources, while filling the `SMRTrigger`, the `epoch_id` field take the current epoch ID
@@ -192,3 +226,25 @@ pub struct Lock {
     /// Lock hash.
     pub hash: Hash,
 }
+
+/// A snapshot of the SMR status. State saves it into the Wal on each SMR event, so that the state
+/// machine can be recovered after restart.
+#[derive(Clone, Debug, Display, PartialEq, Eq)]
+#[display(
+    fmt = "SMR status epoch ID {}, round {}, step {:?}",
+    epoch_id,
+    round,
+    step
+)]
+pub struct SMRStatus {
+    /// Epoch ID of the SMR.
+    pub epoch_id: u64,
+    /// Round of the SMR.
+    pub round: u64,
+    /// Step of the SMR.
+    pub step: Step,
+    /// Epoch hash of the SMR.
+    pub epoch_hash: Hash,
+    /// Lock of the SMR.
+    pub lock: Option<Lock>,
+}
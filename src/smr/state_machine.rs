@@ -1,13 +1,16 @@
 use std::ops::BitXor;
 use std::pin::Pin;
-use std::task::{Context, Poll};
+use std::task::{Context as TaskContext, Poll};
 
+use creep::Context;
 use derive_more::Display;
 use futures::channel::mpsc::{unbounded, UnboundedReceiver, UnboundedSender};
-use futures::stream::Stream;
+use futures::stream::{FusedStream, Stream};
 use log::{debug, error, info};
 
-use crate::smr::smr_types::{Lock, SMREvent, SMRTrigger, Step, TriggerSource, TriggerType};
+use crate::smr::smr_types::{
+    Lock, SMREvent, SMRStatus, SMRTrigger, Step, TriggerSource, TriggerType,
+};
 use crate::{error::ConsensusError, smr::Event, types::Hash};
 use crate::{ConsensusResult, INIT_EPOCH_ID, INIT_ROUND};
 
@@ -22,14 +25,14 @@ pub struct StateMachine {
     epoch_hash: Hash,
     lock:          Option<Lock>,
 
-    event:   (UnboundedSender<SMREvent>, UnboundedSender<SMREvent>),
-    trigger: UnboundedReceiver<SMRTrigger>,
+    event:   (UnboundedSender<(Context, SMREvent)>, UnboundedSender<(Context, SMREvent)>),
+    trigger: UnboundedReceiver<(Context, SMRTrigger)>,
 }
 
 impl Stream for StateMachine {
     type Item = ConsensusError;
 
-    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context) -> Poll<Option<Self::Item>> {
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut TaskContext) -> Poll<Option<Self::Item>> {
         match Stream::poll_next(Pin::new(&mut self.trigger), cx) {
             Poll::Pending => Poll::Pending,
 
@@ -40,19 +43,22 @@ impl Stream for StateMachine {
                     )));
                 }
 
-                let msg = msg.unwrap();
+                let (ctx, msg) = msg.unwrap();
                 let trigger_type = msg.trigger_type.clone();
                 let res = match trigger_type {
-                    TriggerType::NewEpoch(epoch_id) => self.handle_new_epoch(epoch_id, msg.source),
+                    TriggerType::NewEpoch(epoch_id) => {
+                        self.handle_new_epoch(ctx, epoch_id, msg.source)
+                    }
                     TriggerType::Proposal => {
-                        self.handle_proposal(msg.hash, msg.round, msg.source, msg.epoch_id)
+                        self.handle_proposal(ctx, msg.hash, msg.round, msg.source, msg.epoch_id)
                     }
                     TriggerType::PrevoteQC => {
-                        self.handle_prevote(msg.hash, msg.round, msg.source, msg.epoch_id)
+                        self.handle_prevote(ctx, msg.hash, msg.round, msg.source, msg.epoch_id)
                     }
                     TriggerType::PrecommitQC => {
-                        self.handle_precommit(msg.hash, msg.round, msg.source, msg.epoch_id)
+                        self.handle_precommit(ctx, msg.hash, msg.round, msg.source, msg.epoch_id)
                     }
+                    TriggerType::Stop => self.handle_stop(ctx, msg.source),
                 };
 
                 if res.is_err() {
@@ -65,9 +71,15 @@ impl Stream for StateMachine {
     }
 }
 
+impl FusedStream for StateMachine {
+    fn is_terminated(&self) -> bool {
+        self.trigger.is_terminated()
+    }
+}
+
 impl StateMachine {
     /// Create a new state machine.
-    pub fn new(trigger_receiver: UnboundedReceiver<SMRTrigger>) -> (Self, Event, Event) {
+    pub fn new(trigger_receiver: UnboundedReceiver<(Context, SMRTrigger)>) -> (Self, Event, Event) {
         let (tx_1, rx_1) = unbounded();
         let (tx_2, rx_2) = unbounded();
 
@@ -86,7 +98,12 @@ impl StateMachine {
 
     /// Handle a new epoch trigger. If new epoch ID is higher than current, goto a new epoch and
     /// throw a new round info event.
-    fn handle_new_epoch(&mut self, epoch_id: u64, source: TriggerSource) -> ConsensusResult<()> {
+    fn handle_new_epoch(
+        &mut self,
+        ctx: Context,
+        epoch_id: u64,
+        source: TriggerSource,
+    ) -> ConsensusResult<()> {
         info!("Overlord: SMR triggered by new epoch {}", epoch_id);
 
         if source != TriggerSource::State {
@@ -101,7 +118,7 @@ impl StateMachine {
         self.goto_new_epoch(epoch_id);
 
         // throw new round info event
-        self.throw_event(SMREvent::NewRoundInfo {
+        self.throw_event(ctx, SMREvent::NewRoundInfo {
             epoch_id:      self.epoch_id,
             round:         0u64,
             lock_round:    None,
@@ -110,12 +127,27 @@ impl StateMachine {
         Ok(())
     }
 
+    /// Handle a stop trigger. Close the trigger channel and drop the remaining triggers, then throw
+    /// a stop event to the state and the timer. After that, the state machine is terminated.
+    fn handle_stop(&mut self, ctx: Context, source: TriggerSource) -> ConsensusResult<()> {
+        info!("Overlord: SMR triggered by stop");
+
+        if source != TriggerSource::State {
+            return Err(ConsensusError::Other("Stop source error".to_string()));
+        }
+
+        self.trigger.close();
+        while let Ok(Some(_)) = self.trigger.try_next() {}
+        self.throw_event(ctx, SMREvent::Stop)
+    }
+
     /// Handle a proposal trigger. Only if self step is propose, the proposal is valid.
     /// If proposal hash is empty, prevote to an empty hash. If the lock round is some, and the lock
     /// round is higher than self lock round, remove PoLC. Fianlly throw prevote vote event. It is
     /// impossible that the proposal hash is empty with the lock round is some.
     fn handle_proposal(
         &mut self,
+        ctx: Context,
         proposal_hash: Hash,
         lock_round: Option<u64>,
         source: TriggerSource,
@@ -158,10 +190,11 @@ impl StateMachine {
         }
 
         // throw prevote vote event
-        self.throw_event(SMREvent::PrevoteVote {
+        self.throw_event(ctx, SMREvent::PrevoteVote {
             epoch_id:   self.epoch_id,
             round:      self.round,
             epoch_hash: self.epoch_hash.clone(),
+            lock_round: self.lock_round(),
         })?;
         self.goto_step(Step::Prevote);
         Ok(())
@@ -173,6 +206,7 @@ impl StateMachine {
     /// PoLC. Fianlly throw precommit vote event.
     fn handle_prevote(
         &mut self,
+        ctx: Context,
         prevote_hash: Hash,
         prevote_round: Option<u64>,
         source: TriggerSource,
@@ -215,10 +249,11 @@ impl StateMachine {
         }
 
         // throw precommit vote event
-        self.throw_event(SMREvent::PrecommitVote {
+        self.throw_event(ctx, SMREvent::PrecommitVote {
             epoch_id:   self.epoch_id,
             round:      self.round,
             epoch_hash: self.epoch_hash.clone(),
+            lock_round: self.lock_round(),
         })?;
         self.goto_step(Step::Precommit);
         Ok(())
@@ -230,6 +265,7 @@ impl StateMachine {
     /// round. Otherwise, throw commit event.
     fn handle_precommit(
         &mut self,
+        ctx: Context,
         precommit_hash: Hash,
         precommit_round: Option<u64>,
         source: TriggerSource,
@@ -260,7 +296,7 @@ impl StateMachine {
                 .map_or_else(|| (None, None), |lock| (Some(lock.round), Some(lock.hash)));
 
             // throw new round info event
-            self.throw_event(SMREvent::NewRoundInfo {
+            self.throw_event(ctx, SMREvent::NewRoundInfo {
                 epoch_id: self.epoch_id,
                 round: self.round + 1,
                 lock_round,
@@ -275,21 +311,70 @@ impl StateMachine {
                 }
             }
             self.update_polc(precommit_hash.clone(), self.round);
-            self.throw_event(SMREvent::Commit(precommit_hash))?;
+            self.throw_event(ctx, SMREvent::Commit(precommit_hash))?;
             self.goto_step(Step::Commit);
         }
         Ok(())
     }
 
-    fn throw_event(&mut self, event: SMREvent) -> ConsensusResult<()> {
+    /// Recover the state machine from the given status which is loaded from the Wal, and throw
+    /// the event of the recovered step again to resume the consensus process.
+    pub fn recover(&mut self, status: SMRStatus) -> ConsensusResult<()> {
+        info!("Overlord: SMR recover from {}", status);
+        self.epoch_id = status.epoch_id;
+        self.round = status.round;
+        self.goto_step(status.step);
+        self.set_proposal(status.epoch_hash);
+        self.lock = status.lock;
+        self.check()?;
+
+        let event = match self.step {
+            Step::Propose => SMREvent::NewRoundInfo {
+                epoch_id:      self.epoch_id,
+                round:         self.round,
+                lock_round:    self.lock_round(),
+                lock_proposal: self.lock.clone().map(|lock| lock.hash),
+            },
+            Step::Prevote => SMREvent::PrevoteVote {
+                epoch_id:   self.epoch_id,
+                round:      self.round,
+                epoch_hash: self.epoch_hash.clone(),
+                lock_round: self.lock_round(),
+            },
+            Step::Precommit => SMREvent::PrecommitVote {
+                epoch_id:   self.epoch_id,
+                round:      self.round,
+                epoch_hash: self.epoch_hash.clone(),
+                lock_round: self.lock_round(),
+            },
+            Step::Commit => SMREvent::Commit(self.epoch_hash.clone()),
+        };
+        self.throw_event(Context::new(), event)
+    }
+
+    /// Start the state machine at the given epoch, which is the epoch of a trusted checkpoint, and
+    /// throw the new round event of the first round.
+    pub fn start(&mut self, epoch_id: u64) -> ConsensusResult<()> {
+        info!("Overlord: SMR start from epoch {}", epoch_id);
+        self.goto_new_epoch(epoch_id);
+        self.throw_event(Context::new(), SMREvent::NewRoundInfo {
+            epoch_id:      self.epoch_id,
+            round:         INIT_ROUND,
+            lock_round:    None,
+            lock_proposal: None,
+        })
+    }
+
+    /// Throw the event with the context of the trigger to the state and the timer.
+    fn throw_event(&mut self, ctx: Context, event: SMREvent) -> ConsensusResult<()> {
         info!("Overlord: SMR throw {:?} event", event);
         self.event
             .0
-            .unbounded_send(event.clone())
+            .unbounded_send((ctx.clone(), event.clone()))
             .map_err(|_| ConsensusError::ThrowEventErr(format!("{}", event.clone())))?;
         self.event
             .1
-            .unbounded_send(event.clone())
+            .unbounded_send((ctx, event.clone()))
             .map_err(|_| ConsensusError::ThrowEventErr(format!("{}", event)))?;
         Ok(())
     }
@@ -338,6 +423,11 @@ impl StateMachine {
         self.lock = None;
     }
 
+    #[inline]
+    fn lock_round(&self) -> Option<u64> {
+        self.lock.as_ref().map(|lock| lock.round)
+    }
+
     /// Set self proposal hash as the given hash.
     #[inline]
     fn set_proposal(&mut self, proposal_hash: Hash) {
@@ -6,7 +6,12 @@ mod precommit_test;
 mod prevote_test;
 /// Test proposal trigger process.
 mod proposal_test;
+/// Test recover process.
+mod recover_test;
+/// Test stop trigger process.
+mod stop_test;
 
+use creep::Context;
 use futures::channel::mpsc::unbounded;
 use futures::StreamExt;
 use rand::random;
@@ -99,7 +104,7 @@ async fn trigger_test(
 
     let (mut state_machine, mut event, _event) = StateMachine::new(trigger_rx);
     state_machine.set_status(base.round, base.step, base.proposal_hash, base.lock);
-    trigger_tx.unbounded_send(input).unwrap();
+    trigger_tx.unbounded_send((Context::new(), input)).unwrap();
 
     let res = state_machine.next().await;
     if res.is_some() {
@@ -117,7 +122,7 @@ async fn trigger_test(
 
     loop {
         match event.next().await {
-            Some(event) => {
+            Some((_, event)) => {
                 assert_eq!(output, event);
                 return;
             }
@@ -20,6 +20,7 @@ async fn test_prevote_trigger() {
             epoch_id:   0u64,
             round:      0u64,
             epoch_hash: hash.clone(),
+            lock_round: Some(0),
         },
         None,
         Some((0, hash)),
@@ -36,6 +37,7 @@ async fn test_prevote_trigger() {
             epoch_id:   0u64,
             round:      0u64,
             epoch_hash: hash,
+            lock_round: None,
         },
         None,
         None,
@@ -57,6 +59,7 @@ async fn test_prevote_trigger() {
             epoch_id:   0u64,
             round:      1u64,
             epoch_hash: lock_hash.clone(),
+            lock_round: Some(0),
         },
         Some(ConsensusError::SelfCheckErr("".to_string())),
         Some((0, lock_hash)),
@@ -78,6 +81,7 @@ async fn test_prevote_trigger() {
             epoch_id:   0u64,
             round:      1u64,
             epoch_hash: lock_hash.clone(),
+            lock_round: Some(0),
         },
         Some(ConsensusError::SelfCheckErr("".to_string())),
         Some((0, lock_hash)),
@@ -94,6 +98,7 @@ async fn test_prevote_trigger() {
             epoch_id:   0u64,
             round:      1u64,
             epoch_hash: Hash::new(),
+            lock_round: None,
         },
         None,
         None,
@@ -111,6 +116,7 @@ async fn test_prevote_trigger() {
             epoch_id:   0u64,
             round:      1u64,
             epoch_hash: vote_hash.clone(),
+            lock_round: Some(1),
         },
         None,
         Some((1, vote_hash)),
@@ -129,6 +135,7 @@ async fn test_prevote_trigger() {
             epoch_id:   0u64,
             round:      1u64,
             epoch_hash: hash.clone(),
+            lock_round: None,
         },
         None,
         None,
@@ -147,6 +154,7 @@ async fn test_prevote_trigger() {
             epoch_id:   0u64,
             round:      1u64,
             epoch_hash: hash.clone(),
+            lock_round: Some(1),
         },
         None,
         Some((1, hash)),
@@ -163,6 +171,7 @@ async fn test_prevote_trigger() {
     //         epoch_id:   0u64,
     //         round:      1u64,
     //         epoch_hash: Hash::new(),
+    //         lock_round: None,
     //     },
     //     Some(ConsensusError::RoundDiff { local: 1, vote: 0 }),
     //     None,
@@ -179,6 +188,7 @@ async fn test_prevote_trigger() {
             epoch_id:   0u64,
             round:      1u64,
             epoch_hash: Hash::new(),
+            lock_round: None,
         },
         Some(ConsensusError::RoundDiff { local: 1, vote: 2 }),
         None,
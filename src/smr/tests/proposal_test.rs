@@ -21,6 +21,7 @@ async fn test_proposal_trigger() {
             epoch_id:   0u64,
             round:      0u64,
             epoch_hash: hash,
+            lock_round: None,
         },
         None,
         None,
@@ -38,6 +39,7 @@ async fn test_proposal_trigger() {
             epoch_id:   0u64,
             round:      0u64,
             epoch_hash: hash,
+            lock_round: None,
         },
         None,
         None,
@@ -55,6 +57,7 @@ async fn test_proposal_trigger() {
             epoch_id:   0u64,
             round:      1u64,
             epoch_hash: hash,
+            lock_round: None,
         },
         Some(ConsensusError::ProposalErr("Invalid lock".to_string())),
         None,
@@ -72,6 +75,7 @@ async fn test_proposal_trigger() {
             epoch_id:   0u64,
             round:      1u64,
             epoch_hash: hash,
+            lock_round: None,
         },
         None,
         None,
@@ -89,6 +93,7 @@ async fn test_proposal_trigger() {
             epoch_id:   0u64,
             round:      1u64,
             epoch_hash: hash,
+            lock_round: None,
         },
         Some(ConsensusError::SelfCheckErr("".to_string())),
         None,
@@ -106,6 +111,7 @@ async fn test_proposal_trigger() {
             epoch_id:   0u64,
             round:      1u64,
             epoch_hash: hash,
+            lock_round: None,
         },
         Some(ConsensusError::SelfCheckErr("".to_string())),
         None,
@@ -123,6 +129,7 @@ async fn test_proposal_trigger() {
             epoch_id:   0u64,
             round:      1u64,
             epoch_hash: hash,
+            lock_round: None,
         },
         Some(ConsensusError::SelfCheckErr("".to_string())),
         None,
@@ -140,6 +147,7 @@ async fn test_proposal_trigger() {
             epoch_id:   0u64,
             round:      1u64,
             epoch_hash: hash,
+            lock_round: None,
         },
         Some(ConsensusError::SelfCheckErr("".to_string())),
         None,
@@ -159,6 +167,7 @@ async fn test_proposal_trigger() {
             epoch_id:   0u64,
             round:      1u64,
             epoch_hash: hash,
+            lock_round: None,
         },
         Some(ConsensusError::SelfCheckErr("".to_string())),
         None,
@@ -178,6 +187,7 @@ async fn test_proposal_trigger() {
             epoch_id:   0u64,
             round:      1u64,
             epoch_hash: hash,
+            lock_round: None,
         },
         Some(ConsensusError::SelfCheckErr("".to_string())),
         None,
@@ -197,6 +207,7 @@ async fn test_proposal_trigger() {
             epoch_id:   0u64,
             round:      1u64,
             epoch_hash: hash,
+            lock_round: None,
         },
         Some(ConsensusError::SelfCheckErr("".to_string())),
         None,
@@ -216,6 +227,7 @@ async fn test_proposal_trigger() {
             epoch_id:   0u64,
             round:      1u64,
             epoch_hash: lock_hash,
+            lock_round: None,
         },
         Some(ConsensusError::SelfCheckErr("".to_string())),
         None,
@@ -235,6 +247,7 @@ async fn test_proposal_trigger() {
             epoch_id:   0u64,
             round:      1u64,
             epoch_hash: lock_hash.clone(),
+            lock_round: Some(0),
         },
         None,
         Some((0, lock_hash)),
@@ -254,6 +267,7 @@ async fn test_proposal_trigger() {
             epoch_id:   0u64,
             round:      1u64,
             epoch_hash: lock_hash.clone(),
+            lock_round: Some(0),
         },
         None,
         Some((0, lock_hash)),
@@ -273,6 +287,7 @@ async fn test_proposal_trigger() {
             epoch_id:   0u64,
             round:      1u64,
             epoch_hash: lock_hash.clone(),
+            lock_round: Some(0),
         },
         Some(ConsensusError::ProposalErr("Invalid lock".to_string())),
         Some((0, lock_hash)),
@@ -293,6 +308,7 @@ async fn test_proposal_trigger() {
             epoch_id:   0u64,
             round:      2u64,
             epoch_hash: lock_hash.clone(),
+            lock_round: Some(1),
         },
         None,
         Some((1, lock_hash)),
@@ -313,6 +329,7 @@ async fn test_proposal_trigger() {
             epoch_id:   0u64,
             round:      3u64,
             epoch_hash: hash,
+            lock_round: None,
         },
         None,
         None,
@@ -332,6 +349,7 @@ async fn test_proposal_trigger() {
             epoch_id:   0u64,
             round:      2u64,
             epoch_hash: lock_hash.clone(),
+            lock_round: Some(1),
         },
         None,
         Some((1, lock_hash)),
@@ -354,6 +372,7 @@ async fn test_proposal_trigger() {
             epoch_id:   0u64,
             round:      2u64,
             epoch_hash: lock_hash.clone(),
+            lock_round: Some(1),
         },
         Some(ConsensusError::CorrectnessErr("Fork".to_string())),
         Some((1, lock_hash)),
@@ -0,0 +1,130 @@
+use futures::channel::mpsc::unbounded;
+use futures::StreamExt;
+
+use crate::smr::smr_types::{Lock, SMREvent, SMRStatus, Step};
+use crate::smr::state_machine::StateMachine;
+use crate::smr::tests::gen_hash;
+use crate::types::Hash;
+
+/// Test state machine recover from a SMR status.
+/// There are a total of *5* test cases.
+#[runtime::test]
+async fn test_recover() {
+    let mut index = 1;
+    let mut test_cases: Vec<(SMRStatus, SMREvent)> = Vec::new();
+
+    // Test case 01:
+    //      recover to the propose step without a lock.
+    // The output should be a new round info event.
+    test_cases.push((
+        gen_status(1, 0, Step::Propose, Hash::new(), None),
+        SMREvent::NewRoundInfo {
+            epoch_id:      1u64,
+            round:         0u64,
+            lock_round:    None,
+            lock_proposal: None,
+        },
+    ));
+
+    // Test case 02:
+    //      recover to the propose step with a lock.
+    // The output should be a new round info event with the lock.
+    let hash = gen_hash();
+    test_cases.push((
+        gen_status(1, 2, Step::Propose, hash.clone(), Some(1)),
+        SMREvent::NewRoundInfo {
+            epoch_id:      1u64,
+            round:         2u64,
+            lock_round:    Some(1),
+            lock_proposal: Some(hash),
+        },
+    ));
+
+    // Test case 03:
+    //      recover to the prevote step.
+    // The output should be a prevote vote event.
+    let hash = gen_hash();
+    test_cases.push((
+        gen_status(1, 0, Step::Prevote, hash.clone(), None),
+        SMREvent::PrevoteVote {
+            epoch_id:   1u64,
+            round:      0u64,
+            epoch_hash: hash,
+            lock_round: None,
+        },
+    ));
+
+    // Test case 04:
+    //      recover to the precommit step with a lock.
+    // The output should be a precommit vote event with the lock round.
+    let hash = gen_hash();
+    test_cases.push((
+        gen_status(1, 1, Step::Precommit, hash.clone(), Some(1)),
+        SMREvent::PrecommitVote {
+            epoch_id:   1u64,
+            round:      1u64,
+            epoch_hash: hash,
+            lock_round: Some(1),
+        },
+    ));
+
+    // Test case 05:
+    //      recover to the commit step.
+    // The output should be a commit event.
+    let hash = gen_hash();
+    test_cases.push((
+        gen_status(1, 0, Step::Commit, hash.clone(), Some(0)),
+        SMREvent::Commit(hash),
+    ));
+
+    for (status, output) in test_cases.into_iter() {
+        println!("Recover test {}/5", index);
+        index += 1;
+
+        let (_trigger_tx, trigger_rx) = unbounded();
+        let (mut state_machine, mut event, _event) = StateMachine::new(trigger_rx);
+        state_machine.recover(status).unwrap();
+        assert_eq!(event.next().await.map(|(_, e)| e), Some(output));
+    }
+    println!("Recover test success");
+}
+
+/// Test state machine start from the epoch of a checkpoint, including the initial epoch.
+#[runtime::test]
+async fn test_start_from_checkpoint() {
+    for epoch_id in vec![0u64, 1, 100].into_iter() {
+        let (_trigger_tx, trigger_rx) = unbounded();
+        let (mut state_machine, mut event, _event) = StateMachine::new(trigger_rx);
+        state_machine.start(epoch_id).unwrap();
+        assert_eq!(
+            event.next().await.map(|(_, e)| e),
+            Some(SMREvent::NewRoundInfo {
+                epoch_id,
+                round: 0u64,
+                lock_round: None,
+                lock_proposal: None,
+            })
+        );
+    }
+}
+
+fn gen_status(
+    epoch_id: u64,
+    round: u64,
+    step: Step,
+    epoch_hash: Hash,
+    lock_round: Option<u64>,
+) -> SMRStatus {
+    let lock = lock_round.map(|round| Lock {
+        round,
+        hash: epoch_hash.clone(),
+    });
+
+    SMRStatus {
+        epoch_id,
+        round,
+        step,
+        epoch_hash,
+        lock,
+    }
+}
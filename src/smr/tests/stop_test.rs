@@ -0,0 +1,41 @@
+use creep::Context;
+use futures::channel::mpsc::unbounded;
+use futures::stream::FusedStream;
+use futures::StreamExt;
+
+use crate::smr::smr_types::{SMREvent, SMRTrigger, TriggerType};
+use crate::smr::state_machine::StateMachine;
+use crate::types::Hash;
+
+/// Test state machine handle a stop trigger. The triggers after the stop trigger are dropped, and
+/// the stop event is thrown to both the state and the timer with the context of the trigger.
+#[runtime::test]
+async fn test_stop() {
+    let (trigger_tx, trigger_rx) = unbounded();
+    let (mut state_machine, mut state_event, mut timer_event) = StateMachine::new(trigger_rx);
+
+    let ctx = Context::new().with_value("stop", 1u64);
+    trigger_tx
+        .unbounded_send((
+            ctx,
+            SMRTrigger::new(Hash::new(), TriggerType::Stop, None, 0),
+        ))
+        .unwrap();
+    let new_epoch = SMRTrigger::new(Hash::new(), TriggerType::NewEpoch(1), None, 0);
+    trigger_tx
+        .unbounded_send((Context::new(), new_epoch.clone()))
+        .unwrap();
+
+    assert_eq!(state_machine.next().await, None);
+    assert!(state_machine.is_terminated());
+    for event in vec![&mut state_event, &mut timer_event].into_iter() {
+        let (ctx, event) = event.next().await.unwrap();
+        assert_eq!(event, SMREvent::Stop);
+        assert_eq!(ctx.get::<u64>("stop"), Some(&1));
+    }
+
+    // The trigger channel is closed.
+    assert!(trigger_tx
+        .unbounded_send((Context::new(), new_epoch))
+        .is_err());
+}
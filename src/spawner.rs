@@ -0,0 +1,84 @@
+use futures::channel::mpsc::{unbounded, UnboundedSender};
+use futures::executor::{LocalSpawner, ThreadPool};
+use futures::future::BoxFuture;
+use futures::task::{LocalSpawnExt, SpawnError};
+use futures::StreamExt;
+use log::error;
+
+use crate::Spawner;
+
+impl Spawner for ThreadPool {
+    fn spawn(&self, future: BoxFuture<'static, ()>) {
+        self.spawn_ok(future);
+    }
+}
+
+#[cfg(feature = "tokio")]
+impl Spawner for tokio::runtime::Handle {
+    fn spawn(&self, future: BoxFuture<'static, ()>) {
+        tokio::runtime::Handle::spawn(self, future);
+    }
+}
+
+/// A spawner of the `futures` `LocalPool`. Since the `LocalSpawner` can not be sent to other
+/// threads, the futures are sent to a task on the local pool, which spawns them locally. So the
+/// local pool must be run for the consensus tasks to make progress.
+#[derive(Clone, Debug)]
+pub struct LocalPoolSpawner {
+    sender: UnboundedSender<BoxFuture<'static, ()>>,
+}
+
+impl LocalPoolSpawner {
+    /// Create a new local pool spawner from the spawner of a `LocalPool`. Return `Err()` if the
+    /// local pool has been dropped.
+    pub fn new(spawner: LocalSpawner) -> Result<Self, SpawnError> {
+        let (tx, mut rx) = unbounded::<BoxFuture<'static, ()>>();
+        let inner = spawner.clone();
+        spawner.spawn_local(async move {
+            while let Some(future) = rx.next().await {
+                if let Err(e) = inner.spawn_local(future) {
+                    error!("Overlord: local pool spawn error {:?}", e);
+                }
+            }
+        })?;
+
+        Ok(LocalPoolSpawner { sender: tx })
+    }
+}
+
+impl Spawner for LocalPoolSpawner {
+    fn spawn(&self, future: BoxFuture<'static, ()>) {
+        if let Err(e) = self.sender.unbounded_send(future) {
+            error!("Overlord: local pool spawner is closed {:?}", e);
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use futures::channel::oneshot;
+    use futures::executor::{LocalPool, ThreadPool};
+    use futures::FutureExt;
+
+    use crate::spawner::LocalPoolSpawner;
+    use crate::Spawner;
+
+    #[test]
+    fn test_local_pool_spawner() {
+        let mut pool = LocalPool::new();
+        let spawner = LocalPoolSpawner::new(pool.spawner()).unwrap();
+        let (tx, rx) = oneshot::channel();
+
+        Spawner::spawn(&spawner, async move { tx.send(1u8).unwrap() }.boxed());
+        assert_eq!(pool.run_until(rx), Ok(1));
+    }
+
+    #[test]
+    fn test_thread_pool_spawner() {
+        let pool = ThreadPool::new().unwrap();
+        let (tx, rx) = oneshot::channel();
+
+        Spawner::spawn(&pool, async move { tx.send(1u8).unwrap() }.boxed());
+        assert_eq!(futures::executor::block_on(rx), Ok(1));
+    }
+}
@@ -3,5 +3,7 @@ mod collection;
 ///
 pub mod process;
 ///
+mod sign_guard;
+///
 #[cfg(test)]
 mod tests;
@@ -1,4 +1,6 @@
 use std::collections::{HashMap, HashSet};
+use std::error::Error;
+use std::fmt::{self, Debug, Formatter};
 use std::marker::PhantomData;
 use std::time::{Duration, Instant};
 use std::{ops::BitXor, sync::Arc};
@@ -7,27 +9,40 @@ use bit_vec::BitVec;
 use bytes::Bytes;
 use creep::Context;
 use derive_more::Display;
-use futures::channel::mpsc::{unbounded, UnboundedReceiver};
-use futures::{select, StreamExt};
-use futures_timer::Delay;
+use futures::channel::mpsc::{unbounded, Receiver, UnboundedReceiver, UnboundedSender};
+use futures::channel::oneshot;
+use futures::future::{self, FutureExt, Shared};
+use futures::{select, Future, StreamExt, TryFutureExt};
 use log::{debug, error, info, warn};
-use parking_lot::Mutex;
+use parking_lot::{Mutex, RwLock};
 use rlp::encode;
 
-use crate::smr::smr_types::{SMREvent, SMRTrigger, TriggerSource, TriggerType};
+use crate::backoff::BackoffPolicy;
+use crate::clock::SystemClock;
+use crate::context;
+use crate::error::{ConsensusError, ErrorPolicy};
+use crate::smr::smr_types::{
+    Lock, SMREvent, SMRStatus, SMRTrigger, Step, TriggerSource, TriggerType,
+};
 use crate::smr::{Event, SMRHandler};
 use crate::state::collection::{ProposalCollector, VoteCollector};
+use crate::state::sign_guard::SignGuard;
 use crate::types::{
-    Address, AggregatedSignature, AggregatedVote, Commit, Hash, OverlordMsg, PoLC, Proof, Proposal,
-    Signature, SignedProposal, SignedVote, Status, Vote, VoteType,
+    Address, AggregatedSignature, AggregatedVote, Checkpoint, Commit, ConsensusEvent,
+    ConsensusStatus, Hash, LastSigned, Node, OverlordMsg, PoLC, Proof, Proposal, Signature,
+    SignedProposal, SignedVote, Status, VerifyResp, Vote, VoteType,
 };
-use crate::{error::ConsensusError, utils::auth_manage::AuthorityManage};
-use crate::{Codec, Consensus, ConsensusResult, Crypto, INIT_EPOCH_ID, INIT_ROUND};
+use crate::utils::{auth_manage::AuthorityManage, event_hub::EventHub, timer_config::TimerConfig};
+use crate::wal::{Wal, WalMsgType, WalRecord};
+use crate::{Clock, Spawner, WalStorage};
+use crate::{Codec, Consensus, ConsensusResult, Crypto, DurationConfig, OverlordConfig};
+use crate::{INIT_EPOCH_ID, INIT_ROUND};
 
 const CHECK_EPOCH_SUCCESS: bool = true;
 const CHECK_EPOCH_FAILED: bool = false;
-const FUTURE_EPOCH_GAP: u64 = 5;
-const FUTURE_ROUND_GAP: u64 = 10;
+
+/// The result of a commit callback with the context, the epoch ID and the retry times.
+pub(crate) type CommitResult = (Context, u64, u32, Result<Status, Box<dyn Error + Send>>);
 
 #[derive(Clone, Debug, Display, PartialEq, Eq)]
 enum MsgType {
@@ -37,89 +52,429 @@ enum MsgType {
     SignedVote,
 }
 
+/// A snapshot of the consensus status that is shared with the overlord handlers. The state
+/// refreshes it as it goes, so that a status query reads it directly without waiting for the state.
+#[derive(Debug)]
+pub struct StatusSnapshot {
+    status:      ConsensusStatus,
+    epoch_start: Option<Instant>,
+    clock:       Arc<dyn Clock>,
+}
+
+impl Default for StatusSnapshot {
+    fn default() -> Self {
+        StatusSnapshot {
+            status:      ConsensusStatus::default(),
+            epoch_start: None,
+            clock:       Arc::new(SystemClock),
+        }
+    }
+}
+
+impl StatusSnapshot {
+    /// Get the consensus status, of which the epoch duration is counted until now.
+    pub fn get(&self) -> ConsensusStatus {
+        let mut status = self.status.clone();
+        if let Some(start) = self.epoch_start {
+            status.epoch_duration = self.clock.now() - start;
+        }
+        status
+    }
+}
+
+/// A signal to cancel the background tasks of the state, which fires when the paired sender is
+/// dropped.
+#[derive(Clone)]
+struct StopSignal(Shared<oneshot::Receiver<()>>);
+
+impl StopSignal {
+    fn new() -> (oneshot::Sender<()>, Self) {
+        let (tx, rx) = oneshot::channel();
+        (tx, StopSignal(rx.shared()))
+    }
+}
+
+impl Debug for StopSignal {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        write!(f, "stop signal")
+    }
+}
+
 /// Overlord state struct. It maintains the local state of the node, and monitor the SMR event. The
 /// `proposals` is used to cache the signed proposals that are with higher epoch ID or round. The
 /// `hash_with_epoch` field saves hash and its corresponding epoch with the current epoch ID and
 /// round. The `votes` field saves all signed votes and quorum certificates which epoch ID is higher
 /// than `current_epoch - 1`.
 #[derive(Debug)]
-pub struct State<T: Codec, S: Codec, F: Consensus<T, S>, C: Crypto> {
-    epoch_id:             u64,
-    round:                u64,
-    state_machine:        SMRHandler,
-    address:              Address,
-    proposals:            ProposalCollector<T>,
-    votes:                VoteCollector,
-    authority:            AuthorityManage,
-    hash_with_epoch:      HashMap<Hash, T>,
-    full_transcation:     Arc<Mutex<HashMap<Hash, bool>>>,
-    check_epoch_rx:       UnboundedReceiver<bool>,
-    is_leader:            bool,
-    leader_address:       Address,
-    last_commit_round:    Option<u64>,
-    last_commit_proposal: Option<Hash>,
-    epoch_start:          Instant,
-    epoch_interval:       u64,
+pub struct State<T: Codec, S: Codec, F: Consensus<T, S>, C: Crypto, W: WalStorage> {
+    epoch_id:              u64,
+    round:                 u64,
+    step:                  Step,
+    lock:                  Option<Lock>,
+    state_machine:         SMRHandler,
+    address:               Address,
+    proposals:             ProposalCollector<T>,
+    votes:                 VoteCollector,
+    authority:             AuthorityManage,
+    hash_with_epoch:       HashMap<Hash, T>,
+    formed_qcs:            HashSet<(u64, u64, VoteType)>,
+    full_transcation:      Arc<Mutex<HashMap<Hash, bool>>>,
+    check_epoch_rx:        UnboundedReceiver<bool>,
+    is_leader:             bool,
+    leader_address:        Address,
+    last_commit_round:     Option<u64>,
+    last_commit_proposal:  Option<Hash>,
+    epoch_start:           Instant,
+    step_start:            Instant,
+    epoch_interval:        u64,
+    timer_config:          Arc<RwLock<TimerConfig>>,
+    future_epoch_gap:      u64,
+    future_round_gap:      u64,
+    check_epoch_timeout:   u64,
+    commit_retry_times:    u32,
+    commit_retry_interval: u64,
+    commit_backoff:        Box<dyn BackoffPolicy>,
+    callback_retries:      u32,
+    callback_interval:     u64,
+    commit_timeout:        Option<u64>,
+    pending_commit:        Option<(Context, Commit<T>)>,
+    commit_retry_tx:       UnboundedSender<(u64, u32)>,
+    commit_stall_tx:       UnboundedSender<u64>,
+    commit_done_tx:        UnboundedSender<CommitResult>,
+    latest_status_tx:      UnboundedSender<(Context, u64, Status)>,
+    stop_tx:               Option<oneshot::Sender<()>>,
+    stop_signal:           StopSignal,
+    smr_terminated:        bool,
+    sign_guard:            SignGuard,
+    own_votes:             Vec<SignedVote>,
+    status_snapshot:       Arc<RwLock<StatusSnapshot>>,
+    event_hub:             EventHub,
+    spawner:               Arc<dyn Spawner>,
+    clock:                 Arc<dyn Clock>,
 
     function: Arc<F>,
     pin_txs:  PhantomData<S>,
     util:     C,
+    wal:      Arc<Wal<W>>,
 }
 
-impl<T, S, F, C> State<T, S, F, C>
+impl<T, S, F, C, W> State<T, S, F, C, W>
 where
     T: Codec + 'static,
     S: Codec,
     F: Consensus<T, S> + 'static,
     C: Crypto,
+    W: WalStorage + 'static,
 {
     /// Create a new state struct.
+    #[allow(clippy::too_many_arguments)]
     pub fn new(
         smr: SMRHandler,
         addr: Address,
-        interval: u64,
+        config: &OverlordConfig,
+        timer: Arc<RwLock<TimerConfig>>,
+        snapshot: Arc<RwLock<StatusSnapshot>>,
         consensus: Arc<F>,
         crypto: C,
+        wal: Wal<W>,
+        event_hub: EventHub,
+        spawner: Arc<dyn Spawner>,
+        clock: Arc<dyn Clock>,
     ) -> Self {
         let (_tx, rx) = unbounded();
+        let (stop_tx, signal) = StopSignal::new();
 
         State {
-            epoch_id:             INIT_EPOCH_ID,
-            round:                INIT_ROUND,
-            state_machine:        smr,
-            address:              addr,
-            proposals:            ProposalCollector::new(),
-            votes:                VoteCollector::new(),
-            authority:            AuthorityManage::new(),
-            hash_with_epoch:      HashMap::new(),
-            full_transcation:     Arc::new(Mutex::new(HashMap::new())),
-            check_epoch_rx:       rx,
-            is_leader:            false,
-            leader_address:       Address::default(),
-            last_commit_round:    None,
+            epoch_id: INIT_EPOCH_ID,
+            round: INIT_ROUND,
+            step: Step::default(),
+            lock: None,
+            state_machine: smr,
+            address: addr,
+            proposals: ProposalCollector::new(),
+            votes: VoteCollector::new(),
+            authority: AuthorityManage::new(),
+            hash_with_epoch: HashMap::new(),
+            formed_qcs: HashSet::new(),
+            full_transcation: Arc::new(Mutex::new(HashMap::new())),
+            check_epoch_rx: rx,
+            is_leader: false,
+            leader_address: Address::default(),
+            last_commit_round: None,
             last_commit_proposal: None,
-            epoch_start:          Instant::now(),
-            epoch_interval:       interval,
+            epoch_start: clock.now(),
+            step_start: clock.now(),
+            epoch_interval: config.interval,
+            timer_config: timer,
+            future_epoch_gap: config.future_epoch_gap,
+            future_round_gap: config.future_round_gap,
+            check_epoch_timeout: config.check_epoch_timeout,
+            commit_retry_times: config.commit_retry_times,
+            commit_retry_interval: config.commit_retry_interval,
+            commit_backoff: config.commit_retry_backoff.build(),
+            callback_retries: config.callback_retry_times,
+            callback_interval: config.callback_retry_interval,
+            commit_timeout: config.commit_timeout,
+            pending_commit: None,
+            commit_retry_tx: unbounded().0,
+            commit_stall_tx: unbounded().0,
+            commit_done_tx: unbounded().0,
+            latest_status_tx: unbounded().0,
+            stop_tx: Some(stop_tx),
+            stop_signal: signal,
+            smr_terminated: false,
+            sign_guard: SignGuard::new(),
+            own_votes: Vec::new(),
+            status_snapshot: snapshot,
+            event_hub,
+            spawner,
+            clock,
 
             function: consensus,
-            pin_txs:  PhantomData,
-            util:     crypto,
+            pin_txs: PhantomData,
+            util: crypto,
+            wal: Arc::new(wal),
+        }
+    }
+
+    /// Consume the state and return the address, the consensus and the crypto, so that they can
+    /// be used to run again.
+    pub fn into_parts(self) -> (Address, Arc<F>, C) {
+        (self.address, self.function, self.util)
+    }
+
+    /// Recover the state from the Wal before running. Load the latest SMR status that saved in the
+    /// Wal, then restore the authority lists, signed proposals, votes, quorum certificates and the
+    /// last commit of that epoch. Return the SMR status to recover the state machine, or return
+    /// `None` if there is nothing to recover. If the epoch ID of a trusted checkpoint is given and
+    /// the Wal is older than it, only the sign guard and the signed votes that the checkpoint may
+    /// re-transmit are recovered, and `None` is returned to start from the checkpoint instead.
+    ///
+    /// **NOTICE**: If the node crashed while committing, the `commit()` interface will be called
+    /// again with the same epoch ID after recovery.
+    pub async fn recover(&mut self, checkpoint: Option<u64>) -> ConsensusResult<Option<SMRStatus>> {
+        let records = self.wal.load_records().await?;
+        if let Some(record) = records
+            .iter()
+            .rev()
+            .find(|record| record.msg_type == WalMsgType::LastSigned)
+        {
+            self.sign_guard.recover(record.decode()?);
+        }
+
+        let status = records
+            .iter()
+            .rev()
+            .find(|record| record.msg_type == WalMsgType::SMRStatus)
+            .map(|record| record.decode::<SMRStatus>())
+            .transpose()?;
+
+        let status = match (status, checkpoint) {
+            (Some(tmp), Some(epoch_id)) if tmp.epoch_id < epoch_id => {
+                info!(
+                    "Overlord: state skip the Wal of epoch ID {}, which is older than the checkpoint",
+                    tmp.epoch_id
+                );
+                self.recover_own_votes(&records, epoch_id)?;
+                return Ok(None);
+            }
+            (Some(tmp), _) => tmp,
+            (None, _) => return Ok(None),
+        };
+
+        info!(
+            "Overlord: state recover from the Wal, epoch ID {}, round {}, step {:?}",
+            status.epoch_id, status.round, status.step
+        );
+
+        let ctx = Context::new();
+        let epoch_id = status.epoch_id;
+        let mut auth_list = self.get_authority_list(ctx.clone(), epoch_id).await?;
+        self.authority.update(&mut auth_list, false);
+
+        if epoch_id > INIT_EPOCH_ID {
+            let mut tmp = self.get_authority_list(ctx.clone(), epoch_id - 1).await?;
+            self.authority.set_last_list(&mut tmp);
+        }
+
+        self.set_wal_epoch(epoch_id).await?;
+        self.recover_own_votes(&records, epoch_id)?;
+        self.epoch_id = epoch_id;
+        self.round = status.round;
+        self.step = status.step.clone();
+        self.lock = status.lock.clone();
+        self.epoch_start = self.clock.now();
+
+        for record in records.into_iter() {
+            match record.msg_type {
+                WalMsgType::SignedProposal => {
+                    let signed_proposal: SignedProposal<T> = record.decode()?;
+                    let proposal = signed_proposal.proposal.clone();
+                    if proposal.epoch_id == epoch_id {
+                        self.hash_with_epoch
+                            .insert(proposal.epoch_hash, proposal.content);
+                        self.proposals
+                            .insert(epoch_id, proposal.round, signed_proposal)?;
+                    }
+                }
+
+                WalMsgType::SignedVote => {
+                    let signed_vote: SignedVote = record.decode()?;
+                    if signed_vote.get_epoch() == epoch_id {
+                        let voter = signed_vote.vote.voter.clone();
+                        self.votes
+                            .insert_vote(signed_vote.get_hash(), signed_vote, voter);
+                    }
+                }
+
+                WalMsgType::AggregatedVote => {
+                    let qc: AggregatedVote = record.decode()?;
+                    if qc.get_epoch() == epoch_id {
+                        self.votes.set_qc(qc);
+                    }
+                }
+
+                WalMsgType::Commit => {
+                    let commit: Commit<T> = record.decode()?;
+                    if commit.epoch_id + 1 == epoch_id {
+                        self.last_commit_round = Some(commit.proof.round);
+                        self.last_commit_proposal = Some(commit.proof.epoch_hash);
+                    }
+                }
+
+                WalMsgType::CheckEpoch => {
+                    let resp: VerifyResp = record.decode()?;
+                    if resp.epoch_id == epoch_id && resp.is_pass {
+                        self.full_transcation.lock().insert(resp.epoch_hash, true);
+                    }
+                }
+
+                WalMsgType::SMRStatus | WalMsgType::LastSigned => (),
+            }
+        }
+
+        self.is_leader = self.is_proposer()?;
+
+        // If the SMR is recovered after the propose step and the epoch has not been checked before
+        // crash, the check epoch process should be done again to get the full transcations signal.
+        if (status.step == Step::Prevote || status.step == Step::Precommit)
+            && !status.epoch_hash.is_empty()
+            && !self.try_get_full_txs(&status.epoch_hash)
+        {
+            if let Some(epoch) = self.hash_with_epoch.get(&status.epoch_hash).cloned() {
+                self.check_epoch(ctx, status.epoch_hash.clone(), epoch)
+                    .await;
+            }
+        }
+        Ok(Some(status))
+    }
+
+    /// Recover the votes signed by the node itself since the epoch before the given one, which are
+    /// re-transmitted instead of signing again.
+    fn recover_own_votes(&mut self, records: &[WalRecord], epoch_id: u64) -> ConsensusResult<()> {
+        for record in records.iter() {
+            if record.msg_type == WalMsgType::SignedVote {
+                let signed_vote: SignedVote = record.decode()?;
+                if signed_vote.vote.voter == self.address && signed_vote.get_epoch() + 1 >= epoch_id
+                {
+                    self.own_votes.push(signed_vote);
+                }
+            }
         }
+        Ok(())
+    }
+
+    /// Start the state from a trusted checkpoint instead of the initial epoch. The authority lists
+    /// and the last commit are taken from the checkpoint, and anything recovered from an older
+    /// epoch is dropped. Return `Err()` if the checkpoint is invalid. The SMR should be started at
+    /// the checkpoint epoch as well.
+    pub async fn start_from_checkpoint(&mut self, checkpoint: Checkpoint) -> ConsensusResult<()> {
+        checkpoint.validate()?;
+        let epoch_id = checkpoint.epoch_id;
+        info!("Overlord: state start from {}", checkpoint);
+        self.set_wal_epoch(epoch_id).await?;
+        let mut auth_list = checkpoint.authority_list;
+        let mut last_list = checkpoint.last_authority_list;
+        self.authority.update(&mut auth_list, false);
+        self.authority.set_last_list(&mut last_list);
+
+        self.epoch_id = epoch_id;
+        self.round = INIT_ROUND;
+        self.step = Step::default();
+        self.lock = None;
+        self.epoch_start = self.clock.now();
+        self.last_commit_round = checkpoint.last_proof.as_ref().map(|proof| proof.round);
+        self.last_commit_proposal = checkpoint.last_proof.map(|proof| proof.epoch_hash);
+
+        // Clear the proposals and votes recovered from an older epoch.
+        self.proposals.flush(epoch_id.saturating_sub(1));
+        self.votes.flush(epoch_id.saturating_sub(1));
+        self.hash_with_epoch.clear();
+        self.formed_qcs.clear();
+        self.full_transcation = Arc::new(Mutex::new(HashMap::new()));
+        self.own_votes.retain(|sv| sv.get_epoch() + 1 >= epoch_id);
+
+        self.event_hub
+            .publish(ConsensusEvent::NewEpoch { epoch_id });
+        Ok(())
     }
 
-    /// Run state module.
+    /// Run state module. Return `Ok(())` after receiving a stop event from the SMR, or a stop
+    /// message when the SMR has terminated. Since each message is synced to the Wal on saving,
+    /// there is nothing to flush before return. The background tasks, such as the callbacks and
+    /// the delays of the commit retry and watchdog, are cancelled on return.
     pub async fn run(
         &mut self,
-        mut rx: UnboundedReceiver<(Context, OverlordMsg<T>)>,
+        rx: &mut Receiver<(Context, OverlordMsg<T>)>,
+        ctrl_rx: &mut UnboundedReceiver<(Context, OverlordMsg<T>)>,
         mut event: Event,
     ) -> ConsensusResult<()> {
         info!("Overlord: state start running");
-        loop {
-            select! {
-                raw = rx.next() => self.handle_msg(raw).await?,
-                evt = event.next() => self.handle_event(evt).await?,
+        // The stop signal fires when the sender is dropped on return, no matter how it returns.
+        let _stop_tx = self.stop_tx.take();
+        self.smr_terminated = false;
+        let (retry_tx, mut retry_rx) = unbounded();
+        self.commit_retry_tx = retry_tx;
+        let (stall_tx, mut stall_rx) = unbounded();
+        self.commit_stall_tx = stall_tx;
+        let (done_tx, mut done_rx) = unbounded();
+        self.commit_done_tx = done_tx;
+        let (latest_tx, mut latest_rx) = unbounded();
+        self.latest_status_tx = latest_tx;
+
+        'run: loop {
+            // Handle the pending rich status and stop messages before the messages from the
+            // network, so that they are never stuck behind the network messages.
+            while let Ok(Some(ctrl)) = ctrl_rx.try_next() {
+                check_fatal(self.handle_msg(Some(ctrl)).await)?;
+                if self.smr_terminated {
+                    break 'run;
+                }
+            }
+            self.refresh_status();
+
+            let res = select! {
+                ctrl = ctrl_rx.next() => self.handle_msg(ctrl).await,
+                raw = rx.next() => self.handle_msg(raw).await,
+                retry = retry_rx.next() => self.handle_commit_retry(retry).await,
+                stall = stall_rx.next() => self.handle_commit_stall(stall).await,
+                done = done_rx.next() => self.handle_commit_done(done).await,
+                latest = latest_rx.next() => self.handle_latest_status(latest).await,
+                evt = event.next() => {
+                    if let Some((_, SMREvent::Stop)) = evt {
+                        break;
+                    }
+                    self.handle_event(evt).await
+                }
+            };
+            check_fatal(res)?;
+            if self.smr_terminated {
+                break;
             }
         }
+
+        info!("Overlord: state stopped");
+        Ok(())
     }
 
     /// A function to handle message from the network. Public this in the crate to do unit tests.
@@ -127,8 +482,9 @@ where
         &mut self,
         msg: Option<(Context, OverlordMsg<T>)>,
     ) -> ConsensusResult<()> {
-        let msg = msg.ok_or_else(|| ConsensusError::Other("Message sender dropped".to_string()))?;
-        let (ctx, raw) = (msg.0, msg.1);
+        let msg =
+            msg.ok_or_else(|| ConsensusError::ChannelErr("Message sender dropped".to_string()))?;
+        let (ctx, raw) = (context::with_msg_kind(&msg.0, &msg.1), msg.1);
 
         match raw {
             OverlordMsg::SignedProposal(sp) => {
@@ -154,15 +510,59 @@ where
 
             OverlordMsg::RichStatus(rs) => self.goto_new_epoch(ctx.clone(), rs, true).await,
 
+            OverlordMsg::Stop => {
+                info!("Overlord: state receive a stop message");
+                // The stop trigger fails if the SMR has terminated, such as by a former stop
+                // message, then no stop event will come. Stop the state at once.
+                if self.state_machine.stop().is_err() {
+                    info!("Overlord: state stop with the SMR terminated");
+                    self.smr_terminated = true;
+                }
+                Ok(())
+            }
+
             // This is for unit tests.
             #[cfg(test)]
             OverlordMsg::Commit(_) => Ok(()),
         }
     }
 
-    /// A function to handle event from the SMR. Public this function in the crate to do unit tests.
-    pub(crate) async fn handle_event(&mut self, event: Option<SMREvent>) -> ConsensusResult<()> {
-        match event.ok_or_else(|| ConsensusError::Other("Event sender dropped".to_string()))? {
+    /// Refresh the status snapshot that is shared with the overlord handlers.
+    fn refresh_status(&self) {
+        let leader = if self.is_leader {
+            self.address.clone()
+        } else {
+            self.leader_address.clone()
+        };
+
+        let status = ConsensusStatus {
+            epoch_id: self.epoch_id,
+            round: self.round,
+            step: self.step.clone(),
+            lock: self.lock.clone(),
+            leader,
+            is_leader: self.is_leader,
+            authority_list: self.authority.get_authority_list(),
+            epoch_duration: self.clock.now() - self.epoch_start,
+        };
+
+        *self.status_snapshot.write() = StatusSnapshot {
+            status,
+            epoch_start: Some(self.epoch_start),
+            clock: Arc::clone(&self.clock),
+        };
+    }
+
+    /// A function to handle event from the SMR. The event comes with the context of the trigger
+    /// which causes it, and the context is passed to the callbacks. Public this function in the
+    /// crate to do unit tests.
+    pub(crate) async fn handle_event(
+        &mut self,
+        event: Option<(Context, SMREvent)>,
+    ) -> ConsensusResult<()> {
+        let (ctx, event) =
+            event.ok_or_else(|| ConsensusError::ChannelErr("Event sender dropped".to_string()))?;
+        match event {
             SMREvent::NewRoundInfo {
                 round,
                 lock_round,
@@ -170,7 +570,7 @@ where
                 ..
             } => {
                 if let Err(e) = self
-                    .handle_new_round(round, lock_round, lock_proposal)
+                    .handle_new_round(ctx, round, lock_round, lock_proposal)
                     .await
                 {
                     error!("Overlord: state handle new round error {:?}", e);
@@ -178,22 +578,33 @@ where
                 Ok(())
             }
 
-            SMREvent::PrevoteVote { epoch_hash, .. } => {
-                if let Err(e) = self.handle_prevote_vote(epoch_hash).await {
+            SMREvent::PrevoteVote {
+                epoch_hash,
+                lock_round,
+                ..
+            } => {
+                if let Err(e) = self.handle_prevote_vote(ctx, epoch_hash, lock_round).await {
                     error!("Overlord: state handle prevote vote error {:?}", e);
                 }
                 Ok(())
             }
 
-            SMREvent::PrecommitVote { epoch_hash, .. } => {
-                if let Err(e) = self.handle_precommit_vote(epoch_hash).await {
+            SMREvent::PrecommitVote {
+                epoch_hash,
+                lock_round,
+                ..
+            } => {
+                if let Err(e) = self
+                    .handle_precommit_vote(ctx, epoch_hash, lock_round)
+                    .await
+                {
                     error!("Overlord: state handle precommit vote error {:?}", e);
                 }
                 Ok(())
             }
 
             SMREvent::Commit(hash) => {
-                if let Err(e) = self.handle_commit(hash).await {
+                if let Err(e) = self.handle_commit(ctx, hash).await {
                     error!("Overlord: state handle commit error {:?}", e);
                 }
                 Ok(())
@@ -207,69 +618,117 @@ where
     /// of the `commit()` interface, or lastest status after the synchronization is completed send
     /// by the overlord handler.
     ///
-    /// If the difference between the status epoch ID and current's over one, get the last authority
-    /// list of the status epoch ID firstly. Then update the epoch ID, authority_list and the epoch
-    /// interval. Since it is possible to have received and cached the current epoch's proposals,
-    /// votes and quorum certificates before, these should be re-checked as goto new epoch. Finally,
-    /// trigger SMR to goto new epoch.
+    /// If the status epoch ID is not higher than the current, which may happen after recovering
+    /// from the Wal, ignore it. If the difference between the status epoch ID and current's
+    /// over one, get the last authority list of the status epoch ID firstly. Then update the
+    /// epoch ID, authority_list and the epoch interval. Since it is possible to have received
+    /// and cached the current epoch's proposals, votes and quorum certificates before, these
+    /// should be re-checked as goto new epoch. Finally, trigger SMR to goto new epoch.
     async fn goto_new_epoch(
         &mut self,
         ctx: Context,
         status: Status,
         get_last_flag: bool,
     ) -> ConsensusResult<()> {
+        if status.epoch_id <= self.epoch_id {
+            warn!(
+                "Overlord: state receive an outdated rich status, epoch ID {}",
+                status.epoch_id
+            );
+            return Ok(());
+        }
+
+        // Get the last authority list and set the epoch of the Wal before updating anything, so
+        // that the state is unchanged if either fails, and the rich status can be sent again.
         let new_epoch_id = status.epoch_id;
+        let last_list = if get_last_flag {
+            Some(
+                self.get_authority_list(ctx.clone(), new_epoch_id - 1)
+                    .await?,
+            )
+        } else {
+            None
+        };
+        self.set_wal_epoch(new_epoch_id).await?;
+
         self.epoch_id = new_epoch_id;
         self.round = INIT_ROUND;
         info!("Overlord: state goto new epoch {}", self.epoch_id);
+        self.event_hub.publish(ConsensusEvent::NewEpoch {
+            epoch_id: new_epoch_id,
+        });
 
         // Update epoch ID and authority list.
-        self.epoch_start = Instant::now();
+        self.epoch_start = self.clock.now();
         let mut auth_list = status.authority_list;
         self.authority.update(&mut auth_list, true);
 
         // If the status' epoch ID is much higher than the current,
-        if get_last_flag {
-            let mut tmp = self
-                .function
-                .get_authority_list(ctx, new_epoch_id - 1)
-                .await
-                .map_err(|err| {
-                    ConsensusError::Other(format!("get authority list error {:?}", err))
-                })?;
+        if let Some(mut tmp) = last_list {
             self.authority.set_last_list(&mut tmp);
         }
 
-        if let Some(interval) = status.interval {
-            self.epoch_interval = interval;
-        }
+        // Update the epoch interval and the timeout ratios, which take effect on the timer from
+        // the new epoch.
+        self.update_timer_config(status.interval, status.timer_config);
 
         // Clear outdated proposals and votes.
         self.proposals.flush(new_epoch_id - 1);
         self.votes.flush(new_epoch_id - 1);
         self.hash_with_epoch.clear();
+        self.formed_qcs.clear();
+        self.pending_commit = None;
+        self.own_votes
+            .retain(|sv| sv.get_epoch() + 1 >= new_epoch_id);
 
         // Re-check proposals that have been in the proposal collector, of the current epoch ID.
+        // The state has gone to the new epoch, so a re-check error is logged instead of returned.
         if let Some(proposals) = self.proposals.get_epoch_proposals(self.epoch_id) {
-            self.re_check_proposals(proposals)?;
+            if let Err(e) = self.re_check_proposals(proposals) {
+                error!("Overlord: state re-check proposals error {:?}", e);
+            }
         }
 
         // Re-check votes and quorum certificates in the vote collector, of the current epoch ID.
         if let Some((votes, qcs)) = self.votes.get_epoch_votes(new_epoch_id) {
-            self.re_check_votes(votes)?;
-            self.re_check_qcs(qcs)?;
+            if let Err(e) = self
+                .re_check_votes(votes)
+                .and_then(|_| self.re_check_qcs(qcs))
+            {
+                error!("Overlord: state re-check votes error {:?}", e);
+            }
         }
 
-        self.state_machine.new_epoch(new_epoch_id)?;
+        self.state_machine.new_epoch(ctx, new_epoch_id)?;
         Ok(())
     }
 
+    fn update_timer_config(&mut self, interval: Option<u64>, duration: Option<DurationConfig>) {
+        let mut timer_config = self.timer_config.write();
+        if let Some(interval) = interval {
+            self.epoch_interval = interval;
+            timer_config.set_interval(interval);
+        }
+
+        if let Some(duration) = duration {
+            if duration.is_valid() {
+                timer_config.update(duration);
+            } else {
+                warn!(
+                    "Overlord: state receive an invalid duration config {:?}",
+                    duration
+                );
+            }
+        }
+    }
+
     /// Handle `NewRoundInfo` event from SMR. Firstly, goto new round and check the `XOR`
     /// relationship between the lock round type and the lock proposal type. Secondly, check if self
     /// is a proposer. If is not a proposer, return `Ok(())` and wait for a signed proposal from the
     /// network. Otherwise, make up a proposal, broadcast it and touch off SMR trigger.
     async fn handle_new_round(
         &mut self,
+        ctx: Context,
         round: u64,
         lock_round: Option<u64>,
         lock_proposal: Option<Hash>,
@@ -277,6 +736,10 @@ where
         info!("Overlord: state goto new round {}", round);
         self.round = round;
         self.is_leader = false;
+        self.event_hub.publish(ConsensusEvent::NewRound {
+            epoch_id: self.epoch_id,
+            round,
+        });
 
         if lock_round.is_some().bitxor(lock_proposal.is_some()) {
             return Err(ConsensusError::ProposalErr(
@@ -284,6 +747,13 @@ where
             ));
         }
 
+        self.save_smr_status(
+            Step::Propose,
+            lock_proposal.clone().unwrap_or_else(Hash::new),
+            lock_round,
+        )
+        .await?;
+
         if lock_proposal.is_none() {
             // Clear full transcation signal.
             self.full_transcation = Arc::new(Mutex::new(HashMap::new()));
@@ -293,9 +763,7 @@ where
         // has, then handle it.
         if !self.is_proposer()? {
             if let Ok(signed_proposal) = self.proposals.get(self.epoch_id, self.round) {
-                return self
-                    .handle_signed_proposal(Context::new(), signed_proposal)
-                    .await;
+                return self.handle_signed_proposal(ctx, signed_proposal).await;
             }
             return Ok(());
         }
@@ -313,13 +781,17 @@ where
         // done by doing this. These things consititute a Proposal. Then sign it and broadcast it to
         // other nodes.
         self.is_leader = true;
-        let ctx = Context::new();
+
+        // If self has proposed in this round before restart, the signed proposal is recovered from
+        // the Wal. Broadcast it again rather than make up a new one.
+        if let Ok(signed_proposal) = self.proposals.get(self.epoch_id, self.round) {
+            if self.is_own_proposal(&signed_proposal) {
+                return self.re_propose(ctx, signed_proposal).await;
+            }
+        }
+
         let (epoch, hash, polc) = if lock_round.is_none() {
-            let (new_epoch, new_hash) =
-                self.function
-                    .get_epoch(ctx.clone(), self.epoch_id)
-                    .await
-                    .map_err(|err| ConsensusError::Other(format!("get epoch error {:?}", err)))?;
+            let (new_epoch, new_hash) = self.get_epoch(ctx.clone(), self.epoch_id).await?;
             (new_epoch, new_hash, None)
         } else {
             let round = lock_round.clone().unwrap();
@@ -353,14 +825,21 @@ where
             proposer:   self.address.clone(),
         };
 
+        let signed_proposal = self.sign_proposal(proposal).await?;
+        self.wal
+            .save(WalMsgType::SignedProposal, encode(&signed_proposal))
+            .await?;
+
         // **TODO: parallelism**
-        self.broadcast(
-            Context::new(),
-            OverlordMsg::SignedProposal(self.sign_proposal(proposal)?),
-        )
-        .await;
+        self.broadcast(ctx.clone(), OverlordMsg::SignedProposal(signed_proposal))
+            .await;
+        self.event_hub.publish(ConsensusEvent::ProposalSent {
+            epoch_id:   self.epoch_id,
+            round:      self.round,
+            epoch_hash: hash.clone(),
+        });
 
-        self.state_machine.trigger(SMRTrigger {
+        self.trigger_smr(ctx.clone(), SMRTrigger {
             trigger_type: TriggerType::Proposal,
             source:       TriggerSource::State,
             hash:         hash.clone(),
@@ -398,17 +877,17 @@ where
         // 1. Outdated proposals
         // 2. Much higher epoch ID
         // 3. Much higher round
-        if epoch_id < self.epoch_id - 1 || (epoch_id == self.epoch_id && round < self.round) {
+        if epoch_id + 1 < self.epoch_id || (epoch_id == self.epoch_id && round < self.round) {
             debug!(
                 "Overlord: state receive an outdated signed proposal, epoch ID {}, round {}",
                 epoch_id, round,
             );
             return Ok(());
-        } else if self.epoch_id + FUTURE_EPOCH_GAP < epoch_id {
+        } else if self.epoch_id + self.future_epoch_gap < epoch_id {
             warn!("Overlord: state receive a much higher epoch's proposal.");
             return Ok(());
-        } else if (epoch_id == self.epoch_id && self.round + FUTURE_ROUND_GAP < round)
-            || (epoch_id > self.epoch_id && round > FUTURE_ROUND_GAP)
+        } else if (epoch_id == self.epoch_id && self.round + self.future_round_gap < round)
+            || (epoch_id > self.epoch_id && round > self.future_round_gap)
         {
             warn!("Overlord: state receive a much higher round's proposal.");
             return Ok(());
@@ -444,7 +923,7 @@ where
 
         // Deal with proposal's epoch ID is equal to the current epoch ID - 1 and round is higher
         // than the last commit round. Retransmit prevote vote to the last commit proposal.
-        if epoch_id == self.epoch_id - 1 {
+        if epoch_id + 1 == self.epoch_id {
             if let Some((last_round, last_proposal)) = self.last_commit_msg()? {
                 if round <= last_round {
                     debug!(
@@ -502,11 +981,21 @@ where
         let hash = proposal.epoch_hash.clone();
         let epoch = proposal.content.clone();
 
+        self.wal
+            .save(WalMsgType::SignedProposal, encode(&signed_proposal))
+            .await?;
         self.hash_with_epoch.insert(hash.clone(), proposal.content);
         self.proposals
             .insert(self.epoch_id, self.round, signed_proposal)?;
+        self.event_hub.publish(ConsensusEvent::ProposalReceived {
+            epoch_id:   self.epoch_id,
+            round:      self.round,
+            epoch_hash: hash.clone(),
+            proposer:   proposal.proposer.clone(),
+        });
+        self.observe_latency(Step::Propose);
 
-        self.state_machine.trigger(SMRTrigger {
+        self.trigger_smr(ctx.clone(), SMRTrigger {
             trigger_type: TriggerType::Proposal,
             source:       TriggerSource::State,
             hash:         hash.clone(),
@@ -527,12 +1016,59 @@ where
         Ok(())
     }
 
-    async fn handle_prevote_vote(&mut self, hash: Hash) -> ConsensusResult<()> {
+    /// Handle a re-proposal that has been proposed by self before restart. Broadcast it and touch
+    /// off SMR trigger.
+    async fn re_propose(
+        &mut self,
+        ctx: Context,
+        signed_proposal: SignedProposal<T>,
+    ) -> ConsensusResult<()> {
+        info!(
+            "Overlord: state re-propose a recovered proposal epoch ID {}, round {}",
+            self.epoch_id, self.round
+        );
+
+        let proposal = signed_proposal.proposal.clone();
+        let hash = proposal.epoch_hash.clone();
+        let lock_round = proposal.lock.as_ref().map(|polc| polc.lock_round);
+        self.hash_with_epoch
+            .entry(hash.clone())
+            .or_insert_with(|| proposal.content.clone());
+
+        self.broadcast(ctx.clone(), OverlordMsg::SignedProposal(signed_proposal))
+            .await;
+        self.event_hub.publish(ConsensusEvent::ProposalSent {
+            epoch_id:   self.epoch_id,
+            round:      self.round,
+            epoch_hash: hash.clone(),
+        });
+
+        self.trigger_smr(ctx.clone(), SMRTrigger {
+            trigger_type: TriggerType::Proposal,
+            source:       TriggerSource::State,
+            hash:         hash.clone(),
+            round:        lock_round,
+            epoch_id:     self.epoch_id,
+        })?;
+
+        self.check_epoch(ctx, hash, proposal.content).await;
+        Ok(())
+    }
+
+    async fn handle_prevote_vote(
+        &mut self,
+        ctx: Context,
+        hash: Hash,
+        lock_round: Option<u64>,
+    ) -> ConsensusResult<()> {
         info!(
             "Overlord: state receive prevote vote event epoch ID {}, round {}",
             self.epoch_id, self.round
         );
 
+        self.save_smr_status(Step::Prevote, hash.clone(), lock_round)
+            .await?;
+
         let prevote = Vote {
             epoch_id:   self.epoch_id,
             round:      self.round,
@@ -541,27 +1077,37 @@ where
             voter:      self.address.clone(),
         };
 
-        let signed_vote = self.sign_vote(prevote)?;
+        let signed_vote = self.sign_vote(prevote).await?;
+        self.wal
+            .save(WalMsgType::SignedVote, encode(&signed_vote))
+            .await?;
 
-        // **TODO: write Wal**
         if self.is_leader {
             self.votes
                 .insert_vote(signed_vote.get_hash(), signed_vote, self.address.clone());
         } else {
-            self.transmit(Context::new(), OverlordMsg::SignedVote(signed_vote))
+            self.transmit(ctx.clone(), OverlordMsg::SignedVote(signed_vote))
                 .await;
         }
 
-        self.vote_process(VoteType::Prevote).await?;
+        self.vote_process(ctx, VoteType::Prevote).await?;
         Ok(())
     }
 
-    async fn handle_precommit_vote(&mut self, hash: Hash) -> ConsensusResult<()> {
+    async fn handle_precommit_vote(
+        &mut self,
+        ctx: Context,
+        hash: Hash,
+        lock_round: Option<u64>,
+    ) -> ConsensusResult<()> {
         info!(
             "Overlord: state received precommit vote event epoch ID {}, round {}",
             self.epoch_id, self.round
         );
 
+        self.save_smr_status(Step::Precommit, hash.clone(), lock_round)
+            .await?;
+
         let precommit = Vote {
             epoch_id:   self.epoch_id,
             round:      self.round,
@@ -570,27 +1116,33 @@ where
             voter:      self.address.clone(),
         };
 
-        let signed_vote = self.sign_vote(precommit)?;
+        let signed_vote = self.sign_vote(precommit).await?;
+        self.wal
+            .save(WalMsgType::SignedVote, encode(&signed_vote))
+            .await?;
 
-        // **TODO: write Wal**
         if self.is_leader {
             self.votes
                 .insert_vote(signed_vote.get_hash(), signed_vote, self.address.clone());
         } else {
-            self.transmit(Context::new(), OverlordMsg::SignedVote(signed_vote))
+            self.transmit(ctx.clone(), OverlordMsg::SignedVote(signed_vote))
                 .await;
         }
 
-        self.vote_process(VoteType::Precommit).await?;
+        self.vote_process(ctx, VoteType::Precommit).await?;
         Ok(())
     }
 
-    async fn handle_commit(&mut self, hash: Hash) -> ConsensusResult<()> {
+    async fn handle_commit(&mut self, ctx: Context, hash: Hash) -> ConsensusResult<()> {
         info!(
             "Overlord: state receive commit event epoch ID {}, round {}",
             self.epoch_id, self.round
         );
 
+        self.save_smr_status(Step::Commit, hash.clone(), Some(self.round))
+            .await?;
+        self.watch_commit(self.epoch_id);
+
         debug!("Overlord: state get origin epoch");
         let epoch = self.epoch_id;
 
@@ -621,16 +1173,153 @@ where
             proof,
         };
 
+        self.wal.save(WalMsgType::Commit, encode(&commit)).await?;
         self.last_commit_round = Some(self.round);
-        self.last_commit_proposal = Some(hash);
-        // **TODO: write Wal**
+        self.last_commit_proposal = Some(hash.clone());
+        self.pending_commit = Some((ctx.clone(), commit.clone()));
+        self.commit(ctx, commit, 0);
+        Ok(())
+    }
 
-        let ctx = Context::new();
-        let status = self
-            .function
-            .commit(ctx.clone(), epoch, commit)
-            .await
-            .map_err(|err| ConsensusError::Other(format!("commit error {:?}", err)))?;
+    /// Handle a commit retry signal. Only retry the pending commit of the current epoch, since the
+    /// epoch may have been committed or skipped by a rich status since the retry is scheduled.
+    async fn handle_commit_retry(&mut self, retry: Option<(u64, u32)>) -> ConsensusResult<()> {
+        let (epoch_id, times) =
+            retry.ok_or_else(|| ConsensusError::ChannelErr("Retry sender dropped".to_string()))?;
+
+        match self.pending_commit.clone() {
+            Some((ctx, commit)) if commit.epoch_id == epoch_id && epoch_id == self.epoch_id => {
+                info!(
+                    "Overlord: state retry commit epoch ID {}, times {}",
+                    epoch_id, times
+                );
+                self.commit(ctx, commit, times);
+                Ok(())
+            }
+            _ => Ok(()),
+        }
+    }
+
+    /// Handle a commit watchdog signal. If the node still stays in the commit step of the epoch,
+    /// watch it again and check the stalled commit.
+    async fn handle_commit_stall(&mut self, stall: Option<u64>) -> ConsensusResult<()> {
+        let epoch_id = stall
+            .ok_or_else(|| ConsensusError::ChannelErr("Watchdog sender dropped".to_string()))?;
+        if epoch_id != self.epoch_id || self.step != Step::Commit {
+            return Ok(());
+        }
+
+        self.watch_commit(epoch_id);
+        let ctx = self
+            .pending_commit
+            .as_ref()
+            .map(|(ctx, _)| ctx.clone())
+            .unwrap_or_else(Context::new);
+        self.check_commit_stalled(ctx, epoch_id);
+        Ok(())
+    }
+
+    /// Handle the latest status returned by the application for a stalled commit. Goto the new
+    /// epoch if the node still stays in the commit step of the stalled epoch.
+    async fn handle_latest_status(
+        &mut self,
+        latest: Option<(Context, u64, Status)>,
+    ) -> ConsensusResult<()> {
+        let (ctx, epoch_id, status) = latest.ok_or_else(|| {
+            ConsensusError::ChannelErr("Latest status sender dropped".to_string())
+        })?;
+        if epoch_id != self.epoch_id || self.step != Step::Commit {
+            return Ok(());
+        }
+
+        self.goto_new_epoch(ctx, status, true).await
+    }
+
+    /// Call the `commit` interface in the background, so that the state keeps handling the commit
+    /// watchdog signals and the rich status while the callback is running. The result is sent back
+    /// to the state and handled by `handle_commit_done`.
+    fn commit(&self, ctx: Context, commit: Commit<T>, times: u32) {
+        let epoch_id = commit.epoch_id;
+        let commit_ctx = self.ctx_with_status(&ctx);
+        let function = Arc::clone(&self.function);
+        let tx = self.commit_done_tx.clone();
+
+        self.spawn_task(async move {
+            let res = function.commit(commit_ctx, epoch_id, commit).await;
+
+            if tx.unbounded_send((ctx, epoch_id, times, res)).is_err() {
+                error!(
+                    "Overlord: state send commit result failed, epoch ID {}",
+                    epoch_id
+                );
+            }
+        });
+    }
+
+    /// Handle the result of a commit callback. Ignore it if the epoch has been left since the
+    /// callback is called, such as by a rich status or the commit watchdog. If it fails, schedule a
+    /// retry after a backoff interval. A rich status that does not advance the epoch is a failure
+    /// as well. After all the retries fail, publish a `CommitFailed` event so that the application
+    /// can resync. Otherwise, goto the new epoch with the returned rich status. Public this in the
+    /// crate to do unit tests.
+    pub(crate) async fn handle_commit_done(
+        &mut self,
+        done: Option<CommitResult>,
+    ) -> ConsensusResult<()> {
+        let (ctx, epoch, times, res) =
+            done.ok_or_else(|| ConsensusError::ChannelErr("Commit sender dropped".to_string()))?;
+
+        let (round, hash) = match self.pending_commit.as_ref() {
+            Some((_, commit)) if commit.epoch_id == epoch && epoch == self.epoch_id => {
+                (commit.proof.round, commit.proof.epoch_hash.clone())
+            }
+            _ => {
+                info!(
+                    "Overlord: state ignore the commit result of the left epoch ID {}",
+                    epoch
+                );
+                return Ok(());
+            }
+        };
+
+        let res = match res {
+            Ok(status) if status.epoch_id <= epoch => Err(Box::new(ConsensusError::Other(format!(
+                "Commit returns the rich status of epoch ID {}",
+                status.epoch_id
+            ))) as Box<dyn Error + Send>),
+            res => res,
+        };
+
+        let status = match res {
+            Ok(status) => status,
+            Err(err) => {
+                if times < self.commit_retry_times {
+                    self.retry_commit_later(epoch, times + 1);
+                } else {
+                    error!(
+                        "Overlord: state commit epoch ID {} failed after {} times",
+                        epoch,
+                        times + 1
+                    );
+                    self.event_hub.publish(ConsensusEvent::CommitFailed {
+                        epoch_id: epoch,
+                        round,
+                        times: times + 1,
+                    });
+                }
+                return Err(ConsensusError::CommitErr {
+                    epoch_id: epoch,
+                    source:   err,
+                });
+            }
+        };
+
+        self.pending_commit = None;
+        self.event_hub.publish(ConsensusEvent::Commit {
+            epoch_id: epoch,
+            round,
+            epoch_hash: hash,
+        });
 
         info!(
             "Overlord: achieve consensus in epoch ID {} costs {} round",
@@ -641,10 +1330,12 @@ where
         let mut auth_list = status.authority_list.clone();
         self.authority.update(&mut auth_list, true);
 
-        let cost = Instant::now() - self.epoch_start;
+        let cost = self.clock.now() - self.epoch_start;
         if self.next_proposer(status.epoch_id)? && cost < Duration::from_millis(self.epoch_interval)
         {
-            Delay::new(Duration::from_millis(self.epoch_interval) - cost).await;
+            self.clock
+                .delay(Duration::from_millis(self.epoch_interval) - cost)
+                .await;
         }
 
         self.goto_new_epoch(ctx, status, false).await?;
@@ -680,17 +1371,17 @@ where
         // 1. Outdated proposals
         // 2. Much higher epoch ID
         // 3. Much higher round
-        if epoch_id < self.epoch_id - 1 || (epoch_id == self.epoch_id && round < self.round) {
+        if epoch_id + 1 < self.epoch_id || (epoch_id == self.epoch_id && round < self.round) {
             debug!(
                 "Overlord: state receive an outdated signed vote, epoch ID {}, round {}",
                 epoch_id, round,
             );
             return Ok(());
-        } else if self.epoch_id + FUTURE_EPOCH_GAP < epoch_id {
+        } else if self.epoch_id + self.future_epoch_gap < epoch_id {
             warn!("Overlord: state receive a much higher epoch's vote.");
             return Ok(());
-        } else if (epoch_id == self.epoch_id && self.round + FUTURE_ROUND_GAP < round)
-            || (epoch_id > self.epoch_id && round > FUTURE_ROUND_GAP)
+        } else if (epoch_id == self.epoch_id && self.round + self.future_round_gap < round)
+            || (epoch_id > self.epoch_id && round > self.future_round_gap)
         {
             warn!("Overlord: state receive a much higher round's vote.");
             return Ok(());
@@ -759,8 +1450,15 @@ where
             self.epoch_id, self.round
         );
 
+        self.wal
+            .save(WalMsgType::AggregatedVote, encode(&qc))
+            .await?;
         self.votes.set_qc(qc.clone());
-        self.broadcast(ctx, OverlordMsg::AggregatedVote(qc)).await;
+        self.broadcast(ctx.clone(), OverlordMsg::AggregatedVote(qc))
+            .await;
+        if self.publish_qc(vote_type.clone(), epoch_hash.clone()) {
+            self.observe_qc_latency(&vote_type);
+        }
 
         if !epoch_hash.is_empty() {
             if vote_type == VoteType::Prevote {
@@ -774,7 +1472,7 @@ where
             "Overlord: state trigger SMR {:?} QC epoch ID {}, round {}",
             vote_type, self.epoch_id, self.round
         );
-        self.state_machine.trigger(SMRTrigger {
+        self.trigger_smr(ctx, SMRTrigger {
             trigger_type: vote_type.clone().into(),
             source:       TriggerSource::State,
             hash:         epoch_hash,
@@ -826,13 +1524,13 @@ where
         // If the vote epoch ID is lower than the current epoch ID - 1, or the vote epoch ID
         // is equal to the current epoch ID and the vote round is lower than the current round,
         // ignore it directly.
-        if epoch_id < self.epoch_id - 1 || (epoch_id == self.epoch_id && round < self.round) {
+        if epoch_id + 1 < self.epoch_id || (epoch_id == self.epoch_id && round < self.round) {
             debug!(
                 "Overlord: state receive an outdated QC, epoch ID {}, round {}",
                 epoch_id, round,
             );
             return Ok(());
-        } else if epoch_id > self.epoch_id && self.epoch_id + FUTURE_EPOCH_GAP > epoch_id {
+        } else if epoch_id > self.epoch_id && self.epoch_id + self.future_epoch_gap > epoch_id {
             debug!(
                 "Overlord: state receive a future QC, epoch ID {}, round {}",
                 epoch_id, round,
@@ -849,7 +1547,9 @@ where
             qc_type.clone(),
         )?;
 
-        if epoch_id == self.epoch_id && round > self.round && self.round + FUTURE_ROUND_GAP > round
+        if epoch_id == self.epoch_id
+            && round > self.round
+            && self.round + self.future_round_gap > round
         {
             debug!(
                 "Overlord: state receive a future QC, epoch ID {}, round {}",
@@ -862,7 +1562,7 @@ where
         // Deal with QC's epoch ID is equal to the current epoch ID - 1 and round is higher than the
         // last commit round. If the QC is a prevoteQC, ignore it. Retransmit precommit vote to the
         // last commit proposal.
-        if epoch_id == self.epoch_id - 1 {
+        if epoch_id + 1 == self.epoch_id {
             if let Some((last_round, last_proposal)) = self.last_commit_msg()? {
                 if round <= last_round || qc_type == VoteType::Precommit {
                     debug!(
@@ -887,7 +1587,13 @@ where
         }
 
         let qc_hash = aggregated_vote.epoch_hash.clone();
+        self.wal
+            .save(WalMsgType::AggregatedVote, encode(&aggregated_vote))
+            .await?;
         self.votes.set_qc(aggregated_vote);
+        if self.publish_qc(qc_type.clone(), qc_hash.clone()) {
+            self.observe_qc_latency(&qc_type);
+        }
 
         debug!("Overlord: state check if get full transcations");
 
@@ -905,7 +1611,7 @@ where
             qc_type, self.epoch_id, self.round
         );
 
-        self.state_machine.trigger(SMRTrigger {
+        self.trigger_smr(ctx, SMRTrigger {
             trigger_type: qc_type.clone().into(),
             source:       TriggerSource::State,
             hash:         epoch_hash,
@@ -928,13 +1634,14 @@ where
     /// exits. If self node is the leader, check if there is signed prevote vote exsits. It
     /// should be noted that when self is the leader, and the vote type is prevote, the process
     /// should be the same as the handle signed vote.
-    async fn vote_process(&mut self, vote_type: VoteType) -> ConsensusResult<()> {
+    async fn vote_process(&mut self, ctx: Context, vote_type: VoteType) -> ConsensusResult<()> {
         if !self.is_leader {
             if let Ok(qc) = self
                 .votes
                 .get_qc(self.epoch_id, self.round, vote_type.clone())
             {
                 let mut epoch_hash = qc.epoch_hash.clone();
+                self.publish_qc(vote_type.clone(), epoch_hash.clone());
                 if !epoch_hash.is_empty() {
                     if vote_type == VoteType::Prevote {
                         epoch_hash = self.check_full_txs(epoch_hash).await?;
@@ -943,7 +1650,7 @@ where
                     }
                 }
 
-                self.state_machine.trigger(SMRTrigger {
+                self.trigger_smr(ctx, SMRTrigger {
                     trigger_type: qc.vote_type.clone().into(),
                     source:       TriggerSource::State,
                     hash:         epoch_hash,
@@ -960,9 +1667,13 @@ where
             }
         } else if let Some(mut epoch_hash) = self.counting_vote(vote_type.clone())? {
             let qc = self.generate_qc(epoch_hash.clone(), vote_type.clone())?;
+            self.wal
+                .save(WalMsgType::AggregatedVote, encode(&qc))
+                .await?;
             self.votes.set_qc(qc.clone());
-            self.broadcast(Context::new(), OverlordMsg::AggregatedVote(qc))
+            self.broadcast(ctx.clone(), OverlordMsg::AggregatedVote(qc))
                 .await;
+            self.publish_qc(vote_type.clone(), epoch_hash.clone());
 
             if !epoch_hash.is_empty() {
                 if vote_type == VoteType::Prevote {
@@ -977,7 +1688,7 @@ where
                 vote_type, self.epoch_id, self.round
             );
 
-            self.state_machine.trigger(SMRTrigger {
+            self.trigger_smr(ctx, SMRTrigger {
                 trigger_type: vote_type.clone().into(),
                 source:       TriggerSource::State,
                 hash:         epoch_hash,
@@ -994,6 +1705,46 @@ where
         Ok(())
     }
 
+    /// Publish a `QCFormed` event of the current epoch ID and round. The event is published only
+    /// once for each type of QC, although the QC may be received again from the gossip or handled
+    /// again from the vote collector. Return whether the QC is formed for the first time.
+    fn publish_qc(&mut self, vote_type: VoteType, epoch_hash: Hash) -> bool {
+        if !self
+            .formed_qcs
+            .insert((self.epoch_id, self.round, vote_type.clone()))
+        {
+            return false;
+        }
+
+        self.event_hub.publish(ConsensusEvent::QCFormed {
+            epoch_id: self.epoch_id,
+            round: self.round,
+            vote_type,
+            epoch_hash,
+        });
+        true
+    }
+
+    /// Observe the latency of a QC that arrives in the current step. The QCs cached before
+    /// entering the step are not observed, since they arrive at once.
+    fn observe_qc_latency(&self, vote_type: &VoteType) {
+        let step = match vote_type {
+            VoteType::Prevote => Step::Prevote,
+            VoteType::Precommit => Step::Precommit,
+        };
+        self.observe_latency(step);
+    }
+
+    /// Observe the latency of the current step for the adaptive timeouts. The latency is ignored
+    /// if the state is not in the given step.
+    fn observe_latency(&self, step: Step) {
+        if self.step == step {
+            self.timer_config
+                .write()
+                .observe(&step, self.clock.now() - self.step_start);
+        }
+    }
+
     fn counting_vote(&mut self, vote_type: VoteType) -> ConsensusResult<Option<Hash>> {
         let len = self
             .votes
@@ -1170,13 +1921,35 @@ where
         Ok(false)
     }
 
+    /// Check whether the signed proposal is proposed by self in the current epoch ID and round.
+    fn is_own_proposal(&self, signed_proposal: &SignedProposal<T>) -> bool {
+        let proposal = &signed_proposal.proposal;
+        proposal.proposer == self.address
+            && self
+                .verify_signature(
+                    self.util.hash(Bytes::from(encode(proposal))),
+                    signed_proposal.signature.clone(),
+                    &self.address,
+                    MsgType::SignedProposal,
+                )
+                .is_ok()
+    }
+
     fn next_proposer(&self, seed: u64) -> ConsensusResult<bool> {
         let proposer = self.authority.get_proposer(seed, true)?;
         Ok(self.address == proposer)
     }
 
-    fn sign_proposal(&self, proposal: Proposal<T>) -> ConsensusResult<SignedProposal<T>> {
+    async fn sign_proposal(&mut self, proposal: Proposal<T>) -> ConsensusResult<SignedProposal<T>> {
         debug!("Overlord: state sign a proposal");
+        self.guard_sign(LastSigned {
+            epoch_id:   proposal.epoch_id,
+            round:      proposal.round,
+            step:       Step::Propose,
+            epoch_hash: proposal.epoch_hash.clone(),
+        })
+        .await?;
+
         let signature = self
             .util
             .sign(self.util.hash(Bytes::from(encode(&proposal))))
@@ -1188,13 +1961,51 @@ where
         })
     }
 
-    fn sign_vote(&self, vote: Vote) -> ConsensusResult<SignedVote> {
+    async fn sign_vote(&mut self, vote: Vote) -> ConsensusResult<SignedVote> {
         debug!("Overlord: state sign a vote");
+        let step = match vote.vote_type {
+            VoteType::Prevote => Step::Prevote,
+            VoteType::Precommit => Step::Precommit,
+        };
+        self.guard_sign(LastSigned {
+            epoch_id: vote.epoch_id,
+            round: vote.round,
+            step,
+            epoch_hash: vote.epoch_hash.clone(),
+        })
+        .await?;
+
         let signature = self
             .util
             .sign(self.util.hash(Bytes::from(encode(&vote))))
             .map_err(|err| ConsensusError::CryptoErr(format!("{:?}", err)))?;
-        Ok(SignedVote { signature, vote })
+        let signed_vote = SignedVote { signature, vote };
+        if !self.own_votes.contains(&signed_vote) {
+            self.own_votes.push(signed_vote.clone());
+        }
+        Ok(signed_vote)
+    }
+
+    /// Set the epoch of the Wal. The last signed message is saved into the new epoch again, so
+    /// that the sign guard is not flushed with the outdated records after a few epochs without
+    /// signing.
+    async fn set_wal_epoch(&self, epoch_id: u64) -> ConsensusResult<()> {
+        let msgs = self
+            .sign_guard
+            .last_signed()
+            .map(|msg| vec![(WalMsgType::LastSigned, encode(msg))])
+            .unwrap_or_default();
+        self.wal.set_epoch_with(epoch_id, msgs).await
+    }
+
+    /// Check the message with the sign guard. If the message is at a higher position than the last
+    /// signed one, save it to the Wal before signing, so that the guard survives a restart.
+    async fn guard_sign(&mut self, msg: LastSigned) -> ConsensusResult<()> {
+        if self.sign_guard.check(&msg)? {
+            self.wal.save(WalMsgType::LastSigned, encode(&msg)).await?;
+            self.sign_guard.update(msg);
+        }
+        Ok(())
     }
 
     fn aggregate_signatures(
@@ -1282,6 +2093,91 @@ where
         Ok(())
     }
 
+    /// Save the SMR status of the event into the Wal before handling it. If the lock round is some,
+    /// the lock hash is the given epoch hash.
+    async fn save_smr_status(
+        &mut self,
+        step: Step,
+        epoch_hash: Hash,
+        lock_round: Option<u64>,
+    ) -> ConsensusResult<()> {
+        let lock = lock_round.map(|round| Lock {
+            round,
+            hash: epoch_hash.clone(),
+        });
+        self.step = step.clone();
+        self.step_start = self.clock.now();
+        if lock != self.lock {
+            self.event_hub.publish(ConsensusEvent::LockChanged {
+                epoch_id: self.epoch_id,
+                round:    self.round,
+                lock:     lock.clone(),
+            });
+            self.lock = lock.clone();
+        }
+        self.refresh_status();
+
+        let status = SMRStatus {
+            epoch_id: self.epoch_id,
+            round: self.round,
+            step,
+            epoch_hash,
+            lock,
+        };
+        self.wal.save(WalMsgType::SMRStatus, encode(&status)).await
+    }
+
+    /// Spawn a background task of the state, which is cancelled when the state stops running.
+    fn spawn_task<Fut>(&self, task: Fut)
+    where
+        Fut: Future<Output = ()> + Send + 'static,
+    {
+        let stop = self.stop_signal.0.clone();
+        self.spawner.spawn(Box::pin(async move {
+            future::select(Box::pin(task), stop).await;
+        }));
+    }
+
+    /// Trigger the SMR with the context, which is thrown back with the following SMR event and
+    /// used by the callbacks caused by it.
+    fn trigger_smr(&mut self, ctx: Context, trigger: SMRTrigger) -> ConsensusResult<()> {
+        self.state_machine.trigger(ctx, trigger)
+    }
+
+    fn ctx_with_status(&self, ctx: &Context) -> Context {
+        context::with_status(ctx, self.epoch_id, self.round, &self.step)
+    }
+
+    async fn get_epoch(&self, ctx: Context, epoch_id: u64) -> ConsensusResult<(T, Hash)> {
+        let ctx = self.ctx_with_status(&ctx);
+        let function = &self.function;
+        let (times, interval) = (self.callback_retries, self.callback_interval);
+        retry(self.clock.as_ref(), times, interval, move || {
+            function
+                .get_epoch(ctx.clone(), epoch_id)
+                .map_err(move |err| ConsensusError::GetEpochErr {
+                    epoch_id,
+                    source: err,
+                })
+        })
+        .await
+    }
+
+    async fn get_authority_list(&self, ctx: Context, epoch_id: u64) -> ConsensusResult<Vec<Node>> {
+        let ctx = self.ctx_with_status(&ctx);
+        let function = &self.function;
+        let (times, interval) = (self.callback_retries, self.callback_interval);
+        retry(self.clock.as_ref(), times, interval, move || {
+            function
+                .get_authority_list(ctx.clone(), epoch_id)
+                .map_err(move |err| ConsensusError::GetAuthorityListErr {
+                    epoch_id,
+                    source: err,
+                })
+        })
+        .await
+    }
+
     async fn transmit(&self, ctx: Context, msg: OverlordMsg<T>) {
         info!(
             "Overlord: state transmit a message to leader epoch ID {}, round {}",
@@ -1290,7 +2186,11 @@ where
 
         let _ = self
             .function
-            .transmit_to_relayer(ctx, self.leader_address.clone(), msg)
+            .transmit_to_relayer(
+                context::with_msg_kind(&self.ctx_with_status(&ctx), &msg),
+                self.leader_address.clone(),
+                msg,
+            )
             .await
             .map_err(|err| {
                 error!(
@@ -1300,6 +2200,9 @@ where
             });
     }
 
+    /// Re-transmit the vote of the last commit proposal to the leader. Only the vote that self
+    /// signed before is sent again, since a new vote may conflict with the signed messages, such as
+    /// a nil vote in the last commit round.
     async fn retransmit_vote(
         &self,
         ctx: Context,
@@ -1308,22 +2211,30 @@ where
         v_type: VoteType,
         leader_address: Address,
     ) -> ConsensusResult<()> {
-        let vote = Vote {
-            epoch_id:   self.epoch_id - 1,
-            round:      last_round,
-            epoch_hash: hash,
-            voter:      self.address.clone(),
-            vote_type:  v_type,
+        let epoch_id = self.epoch_id - 1;
+        let signed_vote = if let Some(tmp) = self.own_votes.iter().find(|sv| {
+            sv.get_epoch() == epoch_id
+                && sv.get_round() == last_round
+                && sv.vote.vote_type == v_type
+                && sv.get_hash() == hash
+        }) {
+            tmp.clone()
+        } else {
+            debug!(
+                "Overlord: state has not signed the last epoch vote, epoch ID {}, round {}",
+                epoch_id, last_round
+            );
+            return Ok(());
         };
 
         debug!("Overlord: state re-transmit last epoch vote");
-
+        let msg = OverlordMsg::SignedVote(signed_vote);
         let _ = self
             .function
             .transmit_to_relayer(
-                ctx,
+                context::with_msg_kind(&self.ctx_with_status(&ctx), &msg),
                 leader_address,
-                OverlordMsg::SignedVote(self.sign_vote(vote)?),
+                msg,
             )
             .await
             .map_err(|err| {
@@ -1344,7 +2255,10 @@ where
 
         let _ = self
             .function
-            .broadcast_to_other(ctx, msg)
+            .broadcast_to_other(
+                context::with_msg_kind(&self.ctx_with_status(&ctx), &msg),
+                msg,
+            )
             .await
             .map_err(|err| {
                 error!(
@@ -1354,18 +2268,104 @@ where
             });
     }
 
+    fn retry_commit_later(&self, epoch_id: u64, times: u32) {
+        let tx = self.commit_retry_tx.clone();
+        let base = Duration::from_millis(self.commit_retry_interval);
+        let interval = self.commit_backoff.backoff(base, u64::from(times - 1));
+        let delay = self.clock.delay(interval);
+
+        self.spawn_task(async move {
+            delay.await;
+
+            if let Err(e) = tx.unbounded_send((epoch_id, times)) {
+                error!(
+                    "Overlord: state send commit retry failed, epoch ID {}, error {:?}",
+                    epoch_id, e
+                );
+            }
+        });
+    }
+
+    /// Send a watchdog signal of the epoch after the commit timeout if the commit watchdog is
+    /// enabled.
+    fn watch_commit(&self, epoch_id: u64) {
+        let timeout = if let Some(tmp) = self.commit_timeout {
+            tmp
+        } else {
+            return;
+        };
+
+        let tx = self.commit_stall_tx.clone();
+        let watchdog = self.clock.delay(Duration::from_millis(timeout));
+        self.spawn_task(async move {
+            watchdog.await;
+
+            if let Err(e) = tx.unbounded_send(epoch_id) {
+                error!(
+                    "Overlord: state send commit watchdog failed, epoch ID {}, error {:?}",
+                    epoch_id, e
+                );
+            }
+        });
+    }
+
+    /// Publish a `CommitStalled` event and ask the application for the latest status in the
+    /// background, so that a blocking hook does not stall the state. The status is sent back to
+    /// the state if it is higher than the stalled epoch. Public this in the crate to do unit tests.
+    pub(crate) fn check_commit_stalled(&self, ctx: Context, epoch_id: u64) {
+        let duration = self.clock.now() - self.step_start;
+        warn!(
+            "Overlord: state commit stalled epoch ID {}, round {}, duration {:?}",
+            epoch_id, self.round, duration
+        );
+        self.event_hub.publish(ConsensusEvent::CommitStalled {
+            epoch_id,
+            round: self.round,
+            duration,
+        });
+
+        let hook_ctx = self.ctx_with_status(&ctx);
+        let function = Arc::clone(&self.function);
+        let tx = self.latest_status_tx.clone();
+        self.spawn_task(async move {
+            match function.get_latest_status(hook_ctx, epoch_id).await {
+                Ok(Some(status)) if status.epoch_id > epoch_id => {
+                    if tx.unbounded_send((ctx, epoch_id, status)).is_err() {
+                        error!(
+                            "Overlord: state send latest status failed, epoch ID {}",
+                            epoch_id
+                        );
+                    }
+                }
+                Ok(_) => (),
+                Err(err) => {
+                    let err = ConsensusError::GetLatestStatusErr {
+                        epoch_id,
+                        source: err,
+                    };
+                    error!("Overlord: state {}", err);
+                }
+            }
+        });
+    }
+
     async fn check_epoch(&mut self, ctx: Context, hash: Hash, epoch: T) {
+        let ctx = self.ctx_with_status(&ctx);
         let epoch_id = self.epoch_id;
         let round = self.round;
         let tx_signal = Arc::clone(&self.full_transcation);
         let function = Arc::clone(&self.function);
+        let wal = Arc::clone(&self.wal);
         let (new_tx, new_rx) = unbounded();
         let mempool_tx = new_tx.clone();
+        let timeout = self
+            .clock
+            .delay(Duration::from_millis(self.check_epoch_timeout));
         self.check_epoch_rx = new_rx;
 
-        runtime::spawn(async move {
+        self.spawn_task(async move {
             if let Err(e) =
-                check_current_epoch(ctx, function, tx_signal, epoch_id, hash, epoch).await
+                check_current_epoch(ctx, function, wal, tx_signal, epoch_id, hash, epoch).await
             {
                 error!(
                     "Overlord: state check epoch failed, epoch ID {}, round {}, error {:?}",
@@ -1381,8 +2381,8 @@ where
             }
         });
 
-        runtime::spawn(async move {
-            Delay::new(Duration::from_millis(5000)).await;
+        self.spawn_task(async move {
+            timeout.await;
 
             if let Err(e) = new_tx.unbounded_send(CHECK_EPOCH_FAILED) {
                 error!(
@@ -1445,6 +2445,60 @@ where
     //     self.authority.update(&mut authority, false);
     // }
 
+    #[cfg(test)]
+    pub fn get_condition(&self) -> (u64, u64, Step, Option<Lock>) {
+        (
+            self.epoch_id,
+            self.round,
+            self.step.clone(),
+            self.lock.clone(),
+        )
+    }
+
+    #[cfg(test)]
+    pub fn get_last_commit(&self) -> (Option<u64>, Option<Hash>) {
+        (self.last_commit_round, self.last_commit_proposal.clone())
+    }
+
+    #[cfg(test)]
+    pub fn get_vote_collector(&mut self) -> &mut VoteCollector {
+        &mut self.votes
+    }
+
+    #[cfg(test)]
+    pub fn get_hash_with_epoch(&self) -> &HashMap<Hash, T> {
+        &self.hash_with_epoch
+    }
+
+    #[cfg(test)]
+    pub fn set_pending_commit(&mut self, commit: Commit<T>) {
+        self.epoch_id = commit.epoch_id;
+        self.round = commit.proof.round;
+        self.step = Step::Commit;
+        self.pending_commit = Some((Context::new(), commit));
+    }
+
+    #[cfg(test)]
+    pub fn set_commit_retry_tx(&mut self, tx: UnboundedSender<(u64, u32)>) {
+        self.commit_retry_tx = tx;
+    }
+
+    #[cfg(test)]
+    pub fn set_latest_status_tx(&mut self, tx: UnboundedSender<(Context, u64, Status)>) {
+        self.latest_status_tx = tx;
+    }
+
+    #[cfg(test)]
+    pub fn set_event_hub(&mut self, event_hub: EventHub) {
+        self.event_hub = event_hub;
+    }
+
+    #[cfg(test)]
+    pub fn set_last_commit(&mut self, round: u64, hash: Hash) {
+        self.last_commit_round = Some(round);
+        self.last_commit_proposal = Some(hash);
+    }
+
     #[cfg(test)]
     pub fn set_proposal_collector(&mut self, collector: ProposalCollector<T>) {
         self.proposals = collector;
@@ -1461,15 +2515,63 @@ where
         map.insert(hash, true);
     }
 
+    #[cfg(test)]
+    pub fn get_full_transaction(&self, hash: &Hash) -> bool {
+        self.try_get_full_txs(hash)
+    }
+
     #[cfg(test)]
     pub fn set_hash_with_epoch(&mut self, hash_with_epoch: HashMap<Hash, T>) {
         self.hash_with_epoch = hash_with_epoch;
     }
 }
 
-async fn check_current_epoch<U: Consensus<T, S>, T: Codec, S: Codec>(
+/// Call the callback again while it returns an error that should be retried, at most
+/// `retry_times` times. The interval before each retry grows linearly from `retry_interval` as
+/// millisecond.
+async fn retry<R, Fut, Func>(
+    clock: &dyn Clock,
+    retry_times: u32,
+    retry_interval: u64,
+    mut callback: Func,
+) -> ConsensusResult<R>
+where
+    Fut: Future<Output = ConsensusResult<R>>,
+    Func: FnMut() -> Fut,
+{
+    let mut times = 0;
+    loop {
+        match callback().await {
+            Err(e) if e.policy() == ErrorPolicy::Retry && times < retry_times => {
+                times += 1;
+                warn!("Overlord: state retry {} times after {}", times, e);
+                clock
+                    .delay(Duration::from_millis(
+                        retry_interval.saturating_mul(u64::from(times)),
+                    ))
+                    .await;
+            }
+            res => return res,
+        }
+    }
+}
+
+/// Return the error if it is fatal, otherwise log it and go on.
+fn check_fatal(res: ConsensusResult<()>) -> ConsensusResult<()> {
+    match res {
+        Err(e) if e.is_fatal() => Err(e),
+        Err(e) => {
+            error!("Overlord: state handle error {}", e);
+            Ok(())
+        }
+        Ok(()) => Ok(()),
+    }
+}
+
+async fn check_current_epoch<U: Consensus<T, S>, T: Codec, S: Codec, W: WalStorage>(
     ctx: Context,
     function: Arc<U>,
+    wal: Arc<Wal<W>>,
     tx_signal: Arc<Mutex<HashMap<Hash, bool>>>,
     epoch_id: u64,
     hash: Hash,
@@ -1485,13 +2587,34 @@ async fn check_current_epoch<U: Consensus<T, S>, T: Codec, S: Codec>(
 
     let transcations = function
         .check_epoch(ctx, epoch_id, hash.clone(), epoch)
-        .await;
+        .await
+        .map_err(|err| ConsensusError::CheckEpochErr {
+            epoch_id,
+            source: err,
+        });
+
+    if let Err(e) = transcations.as_ref() {
+        warn!("Overlord: state check epoch failed {}", e);
+    }
 
     let res = transcations.is_ok();
-    let mut map = tx_signal.lock();
-    map.insert(hash, res);
+    {
+        let mut map = tx_signal.lock();
+        map.insert(hash.clone(), res);
+    }
     info!("Overlord: state check epoch {}", res);
-    // TODO: write Wal
+
+    // Only the verified epoch hash is saved, so that the failed one can be checked again after
+    // recovery. The Wal may have moved to the next epoch while checking, so the epoch ID is saved
+    // in the response rather than taken from the record.
+    if res {
+        let resp = VerifyResp {
+            epoch_id,
+            epoch_hash: hash,
+            is_pass: true,
+        };
+        wal.save(WalMsgType::CheckEpoch, encode(&resp)).await?;
+    }
     Ok(())
 }
 
@@ -1499,9 +2622,14 @@ async fn check_current_epoch<U: Consensus<T, S>, T: Codec, S: Codec>(
 mod test {
     use std::time::Duration;
 
+    use futures::future::ready;
     use log::info;
     use serde_json::json;
 
+    use crate::clock::SystemClock;
+    use crate::error::ConsensusError;
+    use crate::state::process::retry;
+
     #[test]
     fn test_json() {
         let tmp = Duration::from_millis(200);
@@ -1516,4 +2644,28 @@ mod test {
             })
         );
     }
+
+    #[runtime::test]
+    async fn test_retry() {
+        let mut times = 0;
+        let res: Result<(), _> = retry(&SystemClock, 3, 10, || {
+            times += 1;
+            ready(Err(ConsensusError::GetEpochErr {
+                epoch_id: 1,
+                source:   Box::new(ConsensusError::Other("timeout".to_string())),
+            }))
+        })
+        .await;
+        assert!(res.is_err());
+        assert_eq!(times, 4);
+
+        times = 0;
+        let res: Result<(), _> = retry(&SystemClock, 3, 10, || {
+            times += 1;
+            ready(Err(ConsensusError::ProposalErr(String::new())))
+        })
+        .await;
+        assert!(res.is_err());
+        assert_eq!(times, 1);
+    }
 }
@@ -0,0 +1,127 @@
+use crate::types::LastSigned;
+use crate::{error::ConsensusError, ConsensusResult};
+
+/// A guard in front of signing to prevent double signing, which is similar to the private
+/// validator state of the other BFT engines. It records the last signed message, and refuses to
+/// sign a message whose position is equal to or lower than it, unless it is exactly the same
+/// message that has been signed before.
+#[derive(Debug, Default)]
+pub struct SignGuard {
+    last_signed: Option<LastSigned>,
+}
+
+impl SignGuard {
+    /// Create a new sign guard.
+    pub fn new() -> Self {
+        SignGuard { last_signed: None }
+    }
+
+    /// Recover the sign guard with the last signed message that loaded from the Wal.
+    pub fn recover(&mut self, last_signed: LastSigned) {
+        self.last_signed = Some(last_signed);
+    }
+
+    /// Check whether the message is safe to sign. Return `Ok(true)` if the message is at a higher
+    /// position, which should be persisted and updated before signing. Return `Ok(false)` if the
+    /// message has been signed before.
+    pub fn check(&self, msg: &LastSigned) -> ConsensusResult<bool> {
+        let last = if let Some(tmp) = self.last_signed.as_ref() {
+            tmp
+        } else {
+            return Ok(true);
+        };
+
+        if last == msg {
+            return Ok(false);
+        }
+
+        if (last.epoch_id, last.round, &last.step) >= (msg.epoch_id, msg.round, &msg.step) {
+            return Err(ConsensusError::DoubleSignErr(format!(
+                "refuse to sign {}, last signed {}",
+                msg, last
+            )));
+        }
+        Ok(true)
+    }
+
+    /// Get the last signed message.
+    pub fn last_signed(&self) -> Option<&LastSigned> {
+        self.last_signed.as_ref()
+    }
+
+    /// Update the last signed message.
+    pub fn update(&mut self, msg: LastSigned) {
+        self.last_signed = Some(msg);
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use bytes::Bytes;
+
+    use crate::error::ConsensusError;
+    use crate::smr::smr_types::Step;
+    use crate::state::sign_guard::SignGuard;
+    use crate::types::LastSigned;
+
+    fn gen_msg(epoch_id: u64, round: u64, step: Step, hash: &[u8]) -> LastSigned {
+        LastSigned {
+            epoch_id,
+            round,
+            step,
+            epoch_hash: Bytes::from(hash),
+        }
+    }
+
+    #[test]
+    fn test_sign_guard() {
+        let mut guard = SignGuard::new();
+        let msg = gen_msg(1, 1, Step::Prevote, b"a");
+        assert_eq!(guard.check(&msg), Ok(true));
+        guard.update(msg.clone());
+
+        // Sign the same message again is allowed.
+        assert_eq!(guard.check(&msg), Ok(false));
+
+        // Sign a conflicting message at the same position is refused.
+        let err = ConsensusError::DoubleSignErr(String::new());
+        assert_eq!(guard.check(&gen_msg(1, 1, Step::Prevote, b"b")), Err(err));
+
+        // Sign a message at a lower position is refused.
+        for msg in vec![
+            gen_msg(1, 1, Step::Propose, b"a"),
+            gen_msg(1, 0, Step::Precommit, b"a"),
+            gen_msg(0, 2, Step::Precommit, b"a"),
+        ]
+        .into_iter()
+        {
+            let err = ConsensusError::DoubleSignErr(String::new());
+            assert_eq!(guard.check(&msg), Err(err));
+        }
+
+        // Sign a message at a higher position is allowed.
+        for msg in vec![
+            gen_msg(1, 1, Step::Precommit, b"b"),
+            gen_msg(1, 2, Step::Propose, b"b"),
+            gen_msg(2, 0, Step::Propose, b"b"),
+        ]
+        .into_iter()
+        {
+            assert_eq!(guard.check(&msg), Ok(true));
+        }
+    }
+
+    #[test]
+    fn test_recover() {
+        let mut guard = SignGuard::new();
+        guard.recover(gen_msg(2, 0, Step::Precommit, b"a"));
+
+        let err = ConsensusError::DoubleSignErr(String::new());
+        assert_eq!(guard.check(&gen_msg(2, 0, Step::Prevote, b"a")), Err(err));
+        assert_eq!(
+            guard.check(&gen_msg(2, 0, Step::Precommit, b"a")),
+            Ok(false)
+        );
+        assert_eq!(guard.check(&gen_msg(2, 1, Step::Prevote, b"a")), Ok(true));
+    }
+}
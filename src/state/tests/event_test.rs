@@ -1,16 +1,27 @@
 use std::collections::HashMap;
 use std::sync::Arc;
+use std::time::Duration;
 
-use crossbeam_channel::unbounded;
-use futures::channel::mpsc::unbounded as fut_unbounded;
+use crossbeam_channel::{unbounded, Sender};
+use futures::channel::mpsc::{channel, unbounded as fut_unbounded, UnboundedSender};
+use futures::executor::{LocalPool, ThreadPool};
 use futures::StreamExt;
+use parking_lot::RwLock;
 
-use crate::smr::smr_types::{SMREvent, SMRTrigger};
+use crate::clock::{SystemClock, VirtualClock};
+use crate::error::ConsensusError;
+use crate::smr::smr_types::{Lock, SMREvent, SMRStatus, SMRTrigger, Step};
+use crate::smr::{Event, SMRHandler, SMR};
+use crate::spawner::LocalPoolSpawner;
 use crate::state::collection::VoteCollector;
-use crate::state::process::State;
+use crate::state::process::{CommitResult, State, StatusSnapshot};
 use crate::state::tests::test_utils::{BlsCrypto, ConsensusHelper, Pill};
-use crate::types::{Address, OverlordMsg, VoteType};
-use crate::{smr::SMRHandler, Codec};
+use crate::types::{
+    Address, Checkpoint, ConsensusEvent, OverlordMsg, PoLC, Status, VerifyResp, VoteType,
+};
+use crate::utils::{event_hub::EventHub, timer_config::TimerConfig};
+use crate::wal::WalMsgType;
+use crate::{Clock, Codec, Context, OverlordConfig, Spawner};
 
 use super::*;
 
@@ -49,24 +60,21 @@ async fn handle_event_test(
 ) {
     let (smr_tx, mut smr_rx) = fut_unbounded();
     let (msg_tx, msg_rx) = unbounded();
-
-    let smr_handler = SMRHandler::new(smr_tx);
-    let address = Address::from(vec![0u8]);
-    let helper = ConsensusHelper::new(msg_tx);
-    let crypto = BlsCrypto::new(Address::from(vec![0u8]));
-
-    let mut state = State::new(smr_handler, address, 3000, Arc::new(helper), crypto);
+    let mut state = StateBuilder::new(ConsensusHelper::new(msg_tx)).build(SMRHandler::new(smr_tx));
     update_state(&mut condition, &mut state);
     assert!(condition.proposal_collector.is_none());
     assert!(condition.vote_collector.is_none());
 
-    state.handle_event(Some(input)).await.unwrap();
+    state
+        .handle_event(Some((Context::new(), input)))
+        .await
+        .unwrap();
     assert_eq!(msg_rx.recv().unwrap(), output_msg);
 
     if let Some(tmp) = output_smr {
         loop {
             match smr_rx.next().await {
-                Some(res) => {
+                Some((_, res)) => {
                     assert_eq!(res, tmp);
                     return;
                 }
@@ -87,6 +95,7 @@ async fn test_handle_event() {
         epoch_id:   1u64,
         round:      0u64,
         epoch_hash: epoch_hash(),
+        lock_round: None,
     };
     let condition = Condition::<Pill>::new(1, 0, None, None, None, true);
     let output_msg = gen_signed_vote(1, 0, VoteType::Prevote, epoch_hash());
@@ -103,6 +112,7 @@ async fn test_handle_event() {
         epoch_id:   1u64,
         round:      0u64,
         epoch_hash: epoch_hash(),
+        lock_round: None,
     };
     let condition = Condition::<Pill>::new(1, 0, None, None, None, true);
     let output_msg = gen_signed_vote(1, 0, VoteType::Precommit, epoch_hash());
@@ -145,3 +155,568 @@ async fn test_handle_event() {
     }
     println!("State handle event test success");
 }
+
+#[runtime::test]
+async fn test_commit_stalled() {
+    let (smr_tx, _smr_rx) = fut_unbounded();
+    let (msg_tx, _msg_rx) = unbounded();
+    let config = OverlordConfig::builder()
+        .commit_timeout(1000)
+        .build()
+        .unwrap();
+    let hub = EventHub::new();
+    let mut event_rx = hub.subscribe();
+    let clock = VirtualClock::new();
+    let mut state = StateBuilder::new(ConsensusHelper::new(msg_tx))
+        .config(config)
+        .event_hub(hub)
+        .clock(Arc::new(clock.clone()))
+        .build(SMRHandler::new(smr_tx));
+
+    let (latest_tx, mut latest_rx) = fut_unbounded();
+    state.set_latest_status_tx(latest_tx);
+
+    // The latest status is asked in the background, and sent back if it is higher.
+    clock.advance(Duration::from_millis(1000));
+    state.check_commit_stalled(Context::new(), 0);
+    assert_eq!(
+        event_rx.try_next().unwrap(),
+        Some(ConsensusEvent::CommitStalled {
+            epoch_id: 0,
+            round:    0,
+            duration: Duration::from_millis(1000),
+        })
+    );
+    let (_, epoch_id, status) = latest_rx.next().await.unwrap();
+    assert_eq!(epoch_id, 0);
+    assert_eq!(status.epoch_id, 1);
+}
+
+type TestState = State<Pill, Pill, ConsensusHelper<Pill>, BlsCrypto, MemoryWalStorage>;
+
+/// A builder of the state to test, which is of the address `[0]`. The state uses the default
+/// configuration, a memory Wal, a thread pool and the system clock, unless they are set.
+struct StateBuilder {
+    helper:    ConsensusHelper<Pill>,
+    config:    OverlordConfig,
+    wal:       Wal<MemoryWalStorage>,
+    event_hub: EventHub,
+    spawner:   Arc<dyn Spawner>,
+    clock:     Arc<dyn Clock>,
+}
+
+impl StateBuilder {
+    fn new(helper: ConsensusHelper<Pill>) -> Self {
+        StateBuilder {
+            helper,
+            config: OverlordConfig::default(),
+            wal: gen_wal(),
+            event_hub: EventHub::new(),
+            spawner: Arc::new(ThreadPool::new().unwrap()),
+            clock: Arc::new(SystemClock),
+        }
+    }
+
+    fn config(mut self, config: OverlordConfig) -> Self {
+        self.config = config;
+        self
+    }
+
+    fn storage(mut self, storage: Arc<MemoryWalStorage>) -> Self {
+        self.wal = Wal::new(storage);
+        self
+    }
+
+    fn event_hub(mut self, event_hub: EventHub) -> Self {
+        self.event_hub = event_hub;
+        self
+    }
+
+    fn spawner(mut self, spawner: Arc<dyn Spawner>) -> Self {
+        self.spawner = spawner;
+        self
+    }
+
+    fn clock(mut self, clock: Arc<dyn Clock>) -> Self {
+        self.clock = clock;
+        self
+    }
+
+    fn build(self, smr: SMRHandler) -> TestState {
+        State::new(
+            smr,
+            Address::from(vec![0u8]),
+            &self.config,
+            Arc::new(RwLock::new(TimerConfig::new(&self.config))),
+            Arc::new(RwLock::new(StatusSnapshot::default())),
+            Arc::new(self.helper),
+            BlsCrypto::new(Address::from(vec![0u8])),
+            self.wal,
+            self.event_hub,
+            self.spawner,
+            self.clock,
+        )
+    }
+}
+
+fn gen_state(
+    storage: Arc<MemoryWalStorage>,
+    smr_tx: UnboundedSender<(Context, SMRTrigger)>,
+    msg_tx: Sender<OverlordMsg<Pill>>,
+) -> TestState {
+    StateBuilder::new(ConsensusHelper::new(msg_tx))
+        .storage(storage)
+        .build(SMRHandler::new(smr_tx))
+}
+
+fn gen_rich_status(epoch_id: u64) -> Option<(Context, OverlordMsg<Pill>)> {
+    let status = Status {
+        epoch_id,
+        interval: None,
+        timer_config: None,
+        authority_list: gen_auth_list(),
+    };
+    Some((Context::new(), OverlordMsg::RichStatus(status)))
+}
+
+/// Test that the callbacks caused by a message receive the context of the message, which is
+/// carried through the SMR trigger and event.
+#[runtime::test]
+async fn test_callback_context() {
+    let (mut smr, mut smr_event, _timer_event) = SMR::new();
+    let (msg_tx, msg_rx) = unbounded();
+    let (ctx_tx, ctx_rx) = unbounded();
+    let helper = ConsensusHelper::new(msg_tx).with_ctx_sender(ctx_tx);
+    let mut state = StateBuilder::new(helper).build(smr.take_smr());
+    smr.run(&ThreadPool::new().unwrap());
+
+    // Self is the leader of epoch 3, round 0. The new epoch trigger of the rich status throws a
+    // new round event, and self proposes with the context of the rich status.
+    let ctx = Context::new().with_value("origin", 3u64);
+    let status = gen_rich_status(3).unwrap().1;
+    state.handle_msg(Some((ctx, status))).await.unwrap();
+    let (ctx, event) = smr_event.next().await.unwrap();
+    assert_eq!(ctx.get::<u64>("origin"), Some(&3));
+    state.handle_event(Some((ctx, event))).await.unwrap();
+    assert_eq!(ctx_rx.try_recv().unwrap().get::<u64>("origin"), Some(&3));
+    assert!(msg_rx.try_recv().is_ok());
+
+    // The proposal trigger of self throws a prevote event with the same context.
+    let (ctx, event) = smr_event.next().await.unwrap();
+    assert_eq!(ctx.get::<u64>("origin"), Some(&3));
+    assert_eq!(event, SMREvent::PrevoteVote {
+        epoch_id:   3u64,
+        round:      0u64,
+        epoch_hash: epoch_hash(),
+        lock_round: None,
+    });
+}
+
+#[runtime::test]
+async fn test_sign_guard_after_restart() {
+    let (smr_tx, _smr_rx) = fut_unbounded();
+    let (msg_tx, msg_rx) = unbounded();
+    let storage = Arc::new(MemoryWalStorage::new());
+    let mut state = gen_state(Arc::clone(&storage), smr_tx.clone(), msg_tx.clone());
+
+    let prevote = |hash| {
+        let prevote = SMREvent::PrevoteVote {
+            epoch_id:   1u64,
+            round:      0u64,
+            epoch_hash: hash,
+            lock_round: None,
+        };
+        Some((Context::new(), prevote))
+    };
+
+    // Sign a prevote at epoch 1, then go through two epochs without signing.
+    state.handle_msg(gen_rich_status(1)).await.unwrap();
+    state.handle_event(prevote(epoch_hash())).await.unwrap();
+    let signed_vote = gen_signed_vote(1, 0, VoteType::Prevote, epoch_hash());
+    assert_eq!(msg_rx.try_recv(), Ok(OverlordMsg::SignedVote(signed_vote)));
+    state.handle_msg(gen_rich_status(2)).await.unwrap();
+    state.handle_msg(gen_rich_status(3)).await.unwrap();
+
+    // The recovered sign guard still refuses a conflicting prevote after restart, so that no
+    // vote is sent.
+    let mut state = gen_state(storage, smr_tx, msg_tx);
+    state.recover(None).await.unwrap();
+    state.set_condition(1, 0);
+    state.handle_event(prevote(gen_hash())).await.unwrap();
+    assert!(msg_rx.try_recv().is_err());
+}
+
+#[runtime::test]
+async fn test_retransmit_signed_vote() {
+    let (smr_tx, _smr_rx) = fut_unbounded();
+    let (msg_tx, msg_rx) = unbounded();
+    let mut state = gen_state(Arc::new(MemoryWalStorage::new()), smr_tx, msg_tx);
+
+    let precommit = |round, hash| {
+        let precommit = SMREvent::PrecommitVote {
+            epoch_id: 1u64,
+            round,
+            epoch_hash: hash,
+            lock_round: None,
+        };
+        Some((Context::new(), precommit))
+    };
+    let prevote_qc = |round| {
+        let qc = gen_aggregated_vote(
+            1,
+            round,
+            gen_signature(255),
+            VoteType::Prevote,
+            epoch_hash(),
+            Address::from(vec![1u8]),
+        );
+        Some((Context::new(), OverlordMsg::AggregatedVote(qc)))
+    };
+
+    // Precommit nil in round 0 and the epoch hash in round 1.
+    state.handle_msg(gen_rich_status(1)).await.unwrap();
+    state.handle_event(precommit(0, Hash::new())).await.unwrap();
+    state.set_condition(1, 1);
+    state
+        .handle_event(precommit(1, epoch_hash()))
+        .await
+        .unwrap();
+    while msg_rx.try_recv().is_ok() {}
+    state.handle_msg(gen_rich_status(2)).await.unwrap();
+
+    // The epoch is committed in round 0 by the QC of the leader. The nil precommit must not be
+    // re-signed for the epoch hash.
+    state.set_last_commit(0, epoch_hash());
+    state.handle_msg(prevote_qc(1)).await.unwrap();
+    assert!(msg_rx.try_recv().is_err());
+
+    // The epoch is committed in round 1, re-transmit the signed precommit.
+    state.set_last_commit(1, epoch_hash());
+    state.handle_msg(prevote_qc(2)).await.unwrap();
+    let signed_vote = gen_signed_vote(1, 1, VoteType::Precommit, epoch_hash());
+    assert_eq!(msg_rx.try_recv(), Ok(OverlordMsg::SignedVote(signed_vote)));
+}
+
+#[runtime::test]
+async fn test_recover_older_than_checkpoint() {
+    let (smr_tx, _smr_rx) = fut_unbounded();
+    let (msg_tx, _msg_rx) = unbounded();
+    let storage = Arc::new(MemoryWalStorage::new());
+    let mut state = gen_state(Arc::clone(&storage), smr_tx.clone(), msg_tx.clone());
+
+    state.handle_msg(gen_rich_status(1)).await.unwrap();
+    let prevote = SMREvent::PrevoteVote {
+        epoch_id:   1u64,
+        round:      0u64,
+        epoch_hash: epoch_hash(),
+        lock_round: None,
+    };
+    state
+        .handle_event(Some((Context::new(), prevote)))
+        .await
+        .unwrap();
+
+    // The Wal is recovered unless the checkpoint is higher.
+    let mut state = gen_state(Arc::clone(&storage), smr_tx.clone(), msg_tx.clone());
+    let status = state.recover(Some(1)).await.unwrap().unwrap();
+    assert_eq!(status.epoch_id, 1);
+    let mut state = gen_state(storage, smr_tx, msg_tx);
+    assert!(state.recover(Some(3)).await.unwrap().is_none());
+
+    // A checkpoint without authorities is rejected.
+    let mut checkpoint = Checkpoint {
+        epoch_id:            3,
+        authority_list:      Vec::new(),
+        last_authority_list: gen_auth_list(),
+        last_proof:          None,
+    };
+    assert_eq!(
+        state.start_from_checkpoint(checkpoint.clone()).await,
+        Err(ConsensusError::ConfigErr(String::new()))
+    );
+    checkpoint.authority_list = gen_auth_list();
+    state.start_from_checkpoint(checkpoint).await.unwrap();
+}
+
+fn commit_result(epoch_id: u64, times: u32, status_epoch_id: u64) -> Option<CommitResult> {
+    let status = Status {
+        epoch_id:       status_epoch_id,
+        interval:       None,
+        timer_config:   None,
+        authority_list: gen_auth_list(),
+    };
+    Some((Context::new(), epoch_id, times, Ok(status)))
+}
+
+#[runtime::test]
+async fn test_commit_retry() {
+    let (smr_tx, _smr_rx) = fut_unbounded();
+    let (msg_tx, _msg_rx) = unbounded();
+    let (retry_tx, mut retry_rx) = fut_unbounded();
+    let hub = EventHub::new();
+    let mut event_rx = hub.subscribe();
+    let mut state = gen_state(Arc::new(MemoryWalStorage::new()), smr_tx, msg_tx);
+    state.set_commit_retry_tx(retry_tx);
+    state.set_event_hub(hub);
+    state.set_pending_commit(gen_commit(1, 0, gen_signature(255)));
+
+    // A rich status that does not advance the epoch fails the commit, then a retry is scheduled.
+    assert_eq!(
+        state.handle_commit_done(commit_result(1, 0, 1)).await,
+        Err(ConsensusError::CommitErr {
+            epoch_id: 1,
+            source:   Box::new(ConsensusError::Other(String::new())),
+        })
+    );
+    assert_eq!(retry_rx.next().await, Some((1, 1)));
+    assert!(event_rx.try_next().is_err());
+
+    // After all the retries fail, a commit failed event is published instead of a retry.
+    let times = OverlordConfig::default().commit_retry_times;
+    assert!(state
+        .handle_commit_done(commit_result(1, times, 0))
+        .await
+        .is_err());
+    assert_eq!(
+        event_rx.try_next().unwrap(),
+        Some(ConsensusEvent::CommitFailed {
+            epoch_id: 1,
+            round:    0,
+            times:    times + 1,
+        })
+    );
+}
+
+#[runtime::test]
+async fn test_commit_result_of_left_epoch() {
+    let (smr_tx, _smr_rx) = fut_unbounded();
+    let (msg_tx, _msg_rx) = unbounded();
+    let hub = EventHub::new();
+    let mut event_rx = hub.subscribe();
+    let mut state = gen_state(Arc::new(MemoryWalStorage::new()), smr_tx, msg_tx);
+    state.set_event_hub(hub);
+    state.set_pending_commit(gen_commit(1, 0, gen_signature(255)));
+
+    // The epoch is left by a rich status before the commit callback returns, so that the result
+    // is ignored without publishing any event or retrying.
+    state.set_condition(2, 0);
+    state
+        .handle_commit_done(commit_result(1, 0, 2))
+        .await
+        .unwrap();
+    state
+        .handle_commit_done(commit_result(1, 0, 1))
+        .await
+        .unwrap();
+    assert!(event_rx.try_next().is_err());
+}
+
+#[runtime::test]
+async fn test_check_epoch_after_restart() {
+    let (smr_tx, _smr_rx) = fut_unbounded();
+    let (msg_tx, _msg_rx) = unbounded();
+    let storage = Arc::new(MemoryWalStorage::new());
+    let mut state = gen_state(Arc::clone(&storage), smr_tx.clone(), msg_tx.clone());
+
+    state.handle_msg(gen_rich_status(1)).await.unwrap();
+    let prevote = SMREvent::PrevoteVote {
+        epoch_id:   1u64,
+        round:      0u64,
+        epoch_hash: epoch_hash(),
+        lock_round: None,
+    };
+    state
+        .handle_event(Some((Context::new(), prevote)))
+        .await
+        .unwrap();
+
+    // The checks are saved by a Wal in another epoch, as the Wal may move on while checking.
+    let wal = Wal::new(Arc::clone(&storage));
+    let other_hash = gen_hash();
+    for (epoch_id, hash) in vec![(1u64, epoch_hash()), (2, other_hash.clone())].into_iter() {
+        let resp = VerifyResp {
+            epoch_id,
+            epoch_hash: hash,
+            is_pass: true,
+        };
+        wal.save(WalMsgType::CheckEpoch, rlp::encode(&resp))
+            .await
+            .unwrap();
+    }
+
+    // Only the check of the recovered epoch is restored.
+    let mut state = gen_state(storage, smr_tx, msg_tx);
+    state.recover(None).await.unwrap();
+    assert!(state.get_full_transaction(&epoch_hash()));
+    assert!(!state.get_full_transaction(&other_hash));
+}
+
+#[runtime::test]
+async fn test_recover() {
+    let (smr_tx, _smr_rx) = fut_unbounded();
+    let (msg_tx, msg_rx) = unbounded();
+    let storage = Arc::new(MemoryWalStorage::new());
+
+    // The node commits epoch 1 in round 1, then it locks the epoch hash of epoch 2 in round 0, and
+    // proposes the locked epoch as the leader of round 1 before crash.
+    let prevote_qc = gen_aggregated_vote(
+        2,
+        0,
+        gen_signature(255),
+        VoteType::Prevote,
+        epoch_hash(),
+        Address::from(vec![0u8]),
+    );
+    let lock = Lock {
+        round: 0,
+        hash:  epoch_hash(),
+    };
+    let polc = PoLC {
+        lock_round: 0,
+        lock_votes: prevote_qc.clone(),
+    };
+    let signed_proposal = gen_signed_proposal(2, 1, Some(polc), 0);
+    let status = SMRStatus {
+        epoch_id:   2,
+        round:      1,
+        step:       Step::Propose,
+        epoch_hash: epoch_hash(),
+        lock:       Some(lock.clone()),
+    };
+
+    let wal = Wal::new(Arc::clone(&storage));
+    let records = vec![
+        (
+            WalMsgType::Commit,
+            rlp::encode(&gen_commit(1, 1, gen_signature(255))),
+        ),
+        (
+            WalMsgType::SignedProposal,
+            rlp::encode(&gen_signed_proposal(2, 0, None, 2)),
+        ),
+        (
+            WalMsgType::SignedVote,
+            rlp::encode(&gen_signed_vote(2, 0, VoteType::Prevote, epoch_hash())),
+        ),
+        (WalMsgType::AggregatedVote, rlp::encode(&prevote_qc)),
+        (WalMsgType::SignedProposal, rlp::encode(&signed_proposal)),
+        (WalMsgType::SMRStatus, rlp::encode(&status)),
+    ];
+    for (msg_type, msg) in records.into_iter() {
+        wal.save(msg_type, msg).await.unwrap();
+    }
+
+    let mut state = gen_state(storage, smr_tx, msg_tx);
+    assert_eq!(state.recover(None).await.unwrap(), Some(status));
+    assert_eq!(
+        state.get_condition(),
+        (2, 1, Step::Propose, Some(lock.clone()))
+    );
+    assert_eq!(state.get_last_commit(), (Some(1), Some(epoch_hash())));
+    assert_eq!(
+        state
+            .get_vote_collector()
+            .get_qc(2, 0, VoteType::Prevote)
+            .unwrap(),
+        prevote_qc
+    );
+    assert_eq!(
+        state
+            .get_vote_collector()
+            .vote_count(2, 0, VoteType::Prevote),
+        1
+    );
+    assert_eq!(
+        state.get_hash_with_epoch().get(&epoch_hash()),
+        Some(&Pill::new(2))
+    );
+
+    // The leader broadcasts the recovered proposal again instead of signing a new one.
+    let new_round = SMREvent::NewRoundInfo {
+        epoch_id:      2,
+        round:         1,
+        lock_round:    Some(0),
+        lock_proposal: Some(epoch_hash()),
+    };
+    state
+        .handle_event(Some((Context::new(), new_round)))
+        .await
+        .unwrap();
+    assert_eq!(
+        msg_rx.try_recv(),
+        Ok(OverlordMsg::SignedProposal(signed_proposal))
+    );
+}
+
+#[runtime::test]
+async fn test_qc_formed_once() {
+    let (smr_tx, _smr_rx) = fut_unbounded();
+    let (msg_tx, _msg_rx) = unbounded();
+    let hub = EventHub::new();
+    let mut event_rx = hub.subscribe();
+    let mut state = gen_state(Arc::new(MemoryWalStorage::new()), smr_tx, msg_tx);
+    state.set_event_hub(hub);
+    state.handle_msg(gen_rich_status(1)).await.unwrap();
+
+    // The QC is received twice from the gossip, but it is formed only once.
+    let qc = gen_aggregated_vote(
+        1,
+        0,
+        gen_signature(255),
+        VoteType::Prevote,
+        Hash::new(),
+        Address::from(vec![1u8]),
+    );
+    for _ in 0..2 {
+        let msg = Some((Context::new(), OverlordMsg::AggregatedVote(qc.clone())));
+        state.handle_msg(msg).await.unwrap();
+    }
+
+    let mut count = 0;
+    while let Ok(Some(event)) = event_rx.try_next() {
+        if let ConsensusEvent::QCFormed { .. } = event {
+            count += 1;
+        }
+    }
+    assert_eq!(count, 1);
+}
+
+/// Test that a stop message stops the state when the SMR has terminated, and the background tasks
+/// are cancelled after the state stops.
+#[runtime::test]
+async fn test_stop_with_smr_terminated() {
+    let (smr_tx, smr_rx) = fut_unbounded();
+    let (msg_tx, _msg_rx) = unbounded();
+    let mut pool = LocalPool::new();
+    let clock = VirtualClock::new();
+    let mut state = StateBuilder::new(ConsensusHelper::new(msg_tx))
+        .spawner(Arc::new(LocalPoolSpawner::new(pool.spawner()).unwrap()))
+        .clock(Arc::new(clock.clone()))
+        .build(SMRHandler::new(smr_tx));
+
+    // A failed commit schedules a retry after a delay in the background.
+    state.set_pending_commit(gen_commit(1, 0, gen_signature(255)));
+    assert!(state
+        .handle_commit_done(commit_result(1, 0, 1))
+        .await
+        .is_err());
+    pool.run_until_stalled();
+    assert_eq!(clock.pending_delays(), 1);
+
+    // The stop trigger fails since the SMR has terminated, and the state stops normally.
+    drop(smr_rx);
+    let (_raw_tx, mut raw_rx) = channel(1);
+    let (ctrl_tx, mut ctrl_rx) = fut_unbounded();
+    let (_event_tx, event_rx) = fut_unbounded();
+    ctrl_tx
+        .unbounded_send((Context::new(), OverlordMsg::Stop))
+        .unwrap();
+    state
+        .run(&mut raw_rx, &mut ctrl_rx, Event::new(event_rx))
+        .await
+        .unwrap();
+
+    // The retry delay is cancelled.
+    pool.run_until_stalled();
+    assert_eq!(clock.pending_delays(), 0);
+}
@@ -1,9 +1,11 @@
 #![allow(dead_code)]
 
 mod event_test;
+mod run_test;
 mod test_utils;
 
 use std::collections::HashMap;
+use std::sync::Arc;
 
 use bit_vec::BitVec;
 use bytes::Bytes;
@@ -16,6 +18,7 @@ use crate::types::{
     Address, AggregatedSignature, AggregatedVote, Commit, Hash, Node, PoLC, Proof, Proposal,
     Signature, SignedProposal, SignedVote, Vote, VoteType,
 };
+use crate::wal::{MemoryWalStorage, Wal};
 use crate::Codec;
 
 #[derive(Debug)]
@@ -48,6 +51,10 @@ impl<T: Codec> Condition<T> {
     }
 }
 
+fn gen_wal() -> Wal<MemoryWalStorage> {
+    Wal::new(Arc::new(MemoryWalStorage::new()))
+}
+
 fn gen_hash() -> Hash {
     Hash::from((0..5).map(|_| random::<u8>()).collect::<Vec<_>>())
 }
@@ -152,7 +159,7 @@ fn gen_auth_list() -> Vec<Node> {
 
 fn update_state(
     info: &mut Condition<Pill>,
-    state: &mut State<Pill, Pill, ConsensusHelper<Pill>, BlsCrypto>,
+    state: &mut State<Pill, Pill, ConsensusHelper<Pill>, BlsCrypto, MemoryWalStorage>,
 ) {
     state.set_condition(info.epoch_id, info.round);
 
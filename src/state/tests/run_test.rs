@@ -0,0 +1,101 @@
+use std::sync::Arc;
+use std::time::Duration;
+
+use crossbeam_channel::unbounded;
+use futures::executor::ThreadPool;
+use futures::{pin_mut, select, FutureExt};
+
+use creep::Context;
+
+use crate::clock::SystemClock;
+use crate::error::ConsensusError;
+use crate::state::tests::test_utils::{BlsCrypto, ConsensusHelper, Pill};
+use crate::state::tests::{epoch_hash, gen_auth_list, gen_signed_vote};
+use crate::types::{Address, OverlordMsg, Status, VoteType};
+use crate::wal::MemoryWalStorage;
+use crate::{Clock, Overlord, OverlordConfig, INIT_EPOCH_ID};
+
+#[runtime::test]
+async fn test_stop_and_run_again() {
+    let (msg_tx, _msg_rx) = unbounded();
+    let overlord = Overlord::<Pill, Pill, _, _, _>::new(
+        Address::from(vec![0u8]),
+        Arc::new(ConsensusHelper::new(msg_tx)),
+        BlsCrypto::new(Address::from(vec![0u8])),
+        Arc::new(MemoryWalStorage::new()),
+        Arc::new(ThreadPool::new().unwrap()),
+    );
+    let handler = overlord.get_handler();
+
+    // The status is queried without waiting for the instance to run.
+    assert_eq!(handler.query_status().epoch_id, INIT_EPOCH_ID);
+
+    for _ in 0..2 {
+        // A stop message sent while the instance is not running is dropped.
+        handler.stop().unwrap();
+
+        let run = overlord.run(OverlordConfig::default()).fuse();
+        pin_mut!(run);
+        select! {
+            res = run => panic!("run returns at once {:?}", res),
+            _ = SystemClock.delay(Duration::from_millis(200)).fuse() => (),
+        }
+
+        handler.stop().unwrap();
+        run.await.unwrap();
+    }
+}
+
+/// Test that the messages from the network are refused or wait when the message queue is full,
+/// while the rich status and stop messages never wait.
+#[test]
+fn test_msg_queue_full() {
+    let (msg_tx, _msg_rx) = unbounded();
+    let overlord = Overlord::<Pill, Pill, _, _, _>::new(
+        Address::from(vec![0u8]),
+        Arc::new(ConsensusHelper::new(msg_tx)),
+        BlsCrypto::new(Address::from(vec![0u8])),
+        Arc::new(MemoryWalStorage::new()),
+        Arc::new(ThreadPool::new().unwrap()),
+    );
+    let handler = overlord.get_handler();
+    let vote = || {
+        let vote = gen_signed_vote(1, 0, VoteType::Prevote, epoch_hash());
+        OverlordMsg::SignedVote(vote)
+    };
+
+    // The sender of `try_send_msg` has its own slot besides the capacity.
+    let capacity = OverlordConfig::default().msg_capacity;
+    for _ in 0..=capacity {
+        handler.try_send_msg(Context::new(), vote()).unwrap();
+    }
+    assert_eq!(
+        handler.try_send_msg(Context::new(), vote()),
+        Err(ConsensusError::MsgQueueFull)
+    );
+
+    // So does the sender of `send_msg`, and then it waits for the room of the queue.
+    let sent = handler.send_msg(Context::new(), vote()).now_or_never();
+    assert_eq!(sent, Some(Ok(())));
+    let waiting = handler.send_msg(Context::new(), vote());
+    pin_mut!(waiting);
+    assert!(waiting.as_mut().now_or_never().is_none());
+
+    // The rich status and stop messages are sent to the control queue, which is handled in
+    // priority and never full.
+    let status = OverlordMsg::RichStatus(Status {
+        epoch_id:       1,
+        interval:       None,
+        timer_config:   None,
+        authority_list: gen_auth_list(),
+    });
+    handler
+        .try_send_msg(Context::new(), status.clone())
+        .unwrap();
+    let sent = handler.send_msg(Context::new(), status).now_or_never();
+    assert_eq!(sent, Some(Ok(())));
+    let sent = handler
+        .send_msg(Context::new(), OverlordMsg::Stop)
+        .now_or_never();
+    assert_eq!(sent, Some(Ok(())));
+}
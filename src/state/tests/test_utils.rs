@@ -41,6 +41,7 @@ impl Pill {
 
 pub struct ConsensusHelper<T: Codec> {
     tx:        Sender<OverlordMsg<T>>,
+    ctx_tx:    Option<Sender<Context>>,
     auth_list: Vec<Node>,
 }
 
@@ -76,6 +77,7 @@ impl Consensus<Pill, Pill> for ConsensusHelper<Pill> {
         let status = Status {
             epoch_id:       epoch_id + 1,
             interval:       None,
+            timer_config:   None,
             authority_list: self.auth_list.clone(),
         };
         Ok(status)
@@ -91,28 +93,60 @@ impl Consensus<Pill, Pill> for ConsensusHelper<Pill> {
 
     async fn broadcast_to_other(
         &self,
-        _ctx: Context,
+        ctx: Context,
         msg: OverlordMsg<Pill>,
     ) -> Result<(), Box<dyn Error + Send>> {
+        self.record_ctx(ctx);
         self.tx.send(msg).unwrap();
         Ok(())
     }
 
     async fn transmit_to_relayer(
         &self,
-        _ctx: Context,
+        ctx: Context,
         _addr: Address,
         msg: OverlordMsg<Pill>,
     ) -> Result<(), Box<dyn Error + Send>> {
+        self.record_ctx(ctx);
         self.tx.send(msg).unwrap();
         Ok(())
     }
+
+    async fn get_latest_status(
+        &self,
+        _ctx: Context,
+        epoch_id: u64,
+    ) -> Result<Option<Status>, Box<dyn Error + Send>> {
+        let status = Status {
+            epoch_id:       epoch_id + 1,
+            interval:       None,
+            timer_config:   None,
+            authority_list: self.auth_list.clone(),
+        };
+        Ok(Some(status))
+    }
 }
 
 impl<T: Codec> ConsensusHelper<T> {
     pub fn new(tx: Sender<OverlordMsg<T>>) -> Self {
         let auth_list = gen_auth_list();
-        ConsensusHelper { tx, auth_list }
+        ConsensusHelper {
+            tx,
+            ctx_tx: None,
+            auth_list,
+        }
+    }
+
+    /// Send the context of each broadcast and transmit to the given channel.
+    pub fn with_ctx_sender(mut self, ctx_tx: Sender<Context>) -> Self {
+        self.ctx_tx = Some(ctx_tx);
+        self
+    }
+
+    fn record_ctx(&self, ctx: Context) {
+        if let Some(ctx_tx) = self.ctx_tx.as_ref() {
+            ctx_tx.send(ctx).unwrap();
+        }
     }
 }
 
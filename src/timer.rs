@@ -1,29 +1,39 @@
-use std::task::{Context, Poll};
-use std::time::Duration;
+use std::collections::BTreeMap;
+use std::fmt::{self, Debug, Formatter};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::task::{Context as TaskContext, Poll};
 use std::{future::Future, pin::Pin};
 
+use creep::Context;
 use derive_more::Display;
-use futures::channel::mpsc::{unbounded, UnboundedReceiver, UnboundedSender};
-use futures::stream::{Stream, StreamExt};
-use futures::{FutureExt, SinkExt};
-use futures_timer::Delay;
+use futures::future::BoxFuture;
+use futures::stream::{FusedStream, Stream, StreamExt};
+use futures::FutureExt;
 use log::{debug, error, info};
+use parking_lot::RwLock;
 
-use crate::smr::smr_types::{SMREvent, SMRTrigger, TriggerSource, TriggerType};
+use crate::smr::smr_types::{SMREvent, SMRTrigger, Step, TriggerSource, TriggerType};
 use crate::smr::{Event, SMRHandler};
-use crate::DurationConfig;
+use crate::types::{ConsensusEvent, Hash};
+use crate::utils::{event_hub::EventHub, timer_config::TimerConfig};
 use crate::{error::ConsensusError, ConsensusResult, INIT_EPOCH_ID, INIT_ROUND};
-use crate::{types::Hash, utils::timer_config::TimerConfig};
+use crate::{Clock, Spawner};
 
-/// Overlord timer used futures timer which is powered by a timer heap. When monitor a SMR event,
-/// timer will get timeout interval from timer config, then set a delay. When the timeout expires,
+/// Overlord timer keeps the pending timeouts in a map keyed by the step. When monitor a SMR event,
+/// timer will get timeout interval from timer config, then set a delay of the step. The timeouts of
+/// the previous steps are cancelled as the step advances, so there is at most one timeout of each
+/// step. When the timeout expires, timer will trigger the SMR.
 #[derive(Debug)]
 pub struct Timer {
-    config:        TimerConfig,
+    config:        Arc<RwLock<TimerConfig>>,
     event:         Event,
-    sender:        UnboundedSender<SMREvent>,
-    notify:        UnboundedReceiver<SMREvent>,
+    timers:        BTreeMap<Step, TimeoutInfo>,
+    live_timers:   Arc<AtomicUsize>,
     state_machine: SMRHandler,
+    event_hub:     EventHub,
+    spawner:       Arc<dyn Spawner>,
+    clock:         Arc<dyn Clock>,
     epoch_id:      u64,
     round:         u64,
 }
@@ -32,13 +42,10 @@ pub struct Timer {
 impl Stream for Timer {
     type Item = ConsensusError;
 
-    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context) -> Poll<Option<Self::Item>> {
-        let mut event_ready = true;
-        let mut timer_ready = true;
-
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut TaskContext) -> Poll<Option<Self::Item>> {
         loop {
-            match self.event.poll_next_unpin(cx) {
-                Poll::Pending => event_ready = false,
+            let event_ready = match self.event.poll_next_unpin(cx) {
+                Poll::Pending => false,
 
                 Poll::Ready(event) => {
                     if event.is_none() {
@@ -47,33 +54,26 @@ impl Stream for Timer {
                         )));
                     }
 
-                    let event = event.unwrap();
+                    let (_, event) = event.unwrap();
                     if event == SMREvent::Stop {
+                        self.cancel_timers(None);
                         return Poll::Ready(None);
                     }
                     if let Err(e) = self.set_timer(event) {
                         return Poll::Ready(Some(e));
                     }
+                    true
                 }
             };
 
-            match self.notify.poll_next_unpin(cx) {
-                Poll::Pending => timer_ready = false,
-
-                Poll::Ready(event) => {
-                    if event.is_none() {
-                        return Poll::Ready(Some(ConsensusError::TimerErr(
-                            "Channel terminated".to_string(),
-                        )));
-                    }
-
-                    let event = event.unwrap();
-                    if let Err(e) = self.trigger(event) {
-                        return Poll::Ready(Some(e));
-                    }
+            if let Some(event) = self.poll_timers(cx) {
+                if let Err(e) = self.trigger(event) {
+                    return Poll::Ready(Some(e));
                 }
+                continue;
             }
-            if !event_ready && !timer_ready {
+
+            if !event_ready {
                 return Poll::Pending;
             }
         }
@@ -84,39 +84,48 @@ impl Timer {
     pub fn new(
         event: Event,
         state_machine: SMRHandler,
-        interval: u64,
-        config: Option<DurationConfig>,
+        config: Arc<RwLock<TimerConfig>>,
+        event_hub: EventHub,
+        spawner: Arc<dyn Spawner>,
+        clock: Arc<dyn Clock>,
+        live_timers: Arc<AtomicUsize>,
     ) -> Self {
-        let (tx, rx) = unbounded();
-        let mut timer_config = TimerConfig::new(interval);
-        if let Some(tmp) = config {
-            timer_config.update(tmp);
-        }
-
         Timer {
-            config: timer_config,
+            config,
             epoch_id: INIT_EPOCH_ID,
             round: INIT_ROUND,
-            sender: tx,
-            notify: rx,
             event,
+            timers: BTreeMap::new(),
+            live_timers,
             state_machine,
+            event_hub,
+            spawner,
+            clock,
         }
     }
 
+    /// Run timer module on the spawner. The timer stops when receiving a stop event or the SMR is
+    /// stopped.
     pub fn run(mut self) {
-        runtime::spawn(async move {
+        let spawner = Arc::clone(&self.spawner);
+        spawner.spawn(Box::pin(async move {
             loop {
-                if let Some(err) = self.next().await {
+                let res = self.next().await;
+                if res.is_none() || self.event.is_terminated() {
+                    info!("Overlord: timer stopped");
+                    break;
+                }
+
+                if let Some(err) = res {
                     error!("Overlord: timer error {:?}", err);
                 }
             }
-        });
+            self.cancel_timers(None);
+        }));
     }
 
     fn set_timer(&mut self, event: SMREvent) -> ConsensusResult<()> {
-        let mut is_propose_timer = false;
-        match event.clone() {
+        let step = match event.clone() {
             SMREvent::NewRoundInfo {
                 epoch_id, round, ..
             } => {
@@ -124,31 +133,75 @@ impl Timer {
                     self.epoch_id = epoch_id;
                 }
                 self.round = round;
-                is_propose_timer = true;
+                Step::Propose
+            }
+            SMREvent::PrevoteVote { .. } => Step::Prevote,
+            SMREvent::PrecommitVote { .. } => Step::Precommit,
+            SMREvent::Commit(_) => {
+                self.cancel_timers(None);
+                return Ok(());
             }
-            SMREvent::Commit(_) => return Ok(()),
-            _ => (),
+            SMREvent::Stop => return Ok(()),
         };
 
-        let mut interval = self.config.get_timeout(event.clone())?;
+        let interval = self.config.read().get_timeout(event.clone())?;
+        info!("Overlord: timer set {:?} timer", event);
 
-        if is_propose_timer {
-            let mut coef = self.round as u32;
-            if coef > 10 {
-                coef = 10;
-            }
-            interval *= 2u32.pow(coef);
+        // A new round cancels all the timeouts of the previous round, otherwise only the
+        // timeouts of the previous steps in the round are cancelled.
+        if step == Step::Propose {
+            self.cancel_timers(None);
+        } else {
+            self.cancel_timers(Some(&step));
         }
 
-        info!("Overlord: timer set {:?} timer", event);
+        let smr_timer = TimeoutInfo::new(self.clock.delay(interval), event);
+        self.timers.insert(step, smr_timer);
+        self.update_live_timers();
+        Ok(())
+    }
+
+    /// Cancel the timeouts of the steps before the given step, or all the timeouts if the step is
+    /// `None`.
+    fn cancel_timers(&mut self, before: Option<&Step>) {
+        let cancelled = match before {
+            Some(step) => {
+                let remain = self.timers.split_off(step);
+                let cancelled = self.timers.len();
+                self.timers = remain;
+                cancelled
+            }
+            None => {
+                let cancelled = self.timers.len();
+                self.timers.clear();
+                cancelled
+            }
+        };
 
-        let smr_timer = TimeoutInfo::new(interval, event, self.sender.clone());
+        if cancelled > 0 {
+            debug!("Overlord: timer cancel {} timers", cancelled);
+            self.update_live_timers();
+        }
+    }
 
-        runtime::spawn(async move {
-            smr_timer.await;
-        });
+    /// Poll the pending timeouts, remove and return the first one that expires.
+    fn poll_timers(&mut self, cx: &mut TaskContext) -> Option<SMREvent> {
+        let expired = self
+            .timers
+            .iter_mut()
+            .find_map(|(step, timer)| match timer.poll_unpin(cx) {
+                Poll::Ready(event) => Some((step.clone(), event)),
+                Poll::Pending => None,
+            });
+
+        let (step, event) = expired?;
+        self.timers.remove(&step);
+        self.update_live_timers();
+        Some(event)
+    }
 
-        Ok(())
+    fn update_live_timers(&self) {
+        self.live_timers.store(self.timers.len(), Ordering::Release);
     }
 
     #[rustfmt::skip]
@@ -184,7 +237,19 @@ impl Timer {
 
         debug!("Overlord: timer {:?} time out", event);
 
-        self.state_machine.trigger(SMRTrigger {
+        let step = match trigger_type {
+            TriggerType::Proposal => Step::Propose,
+            TriggerType::PrevoteQC => Step::Prevote,
+            _ => Step::Precommit,
+        };
+        self.config.write().observe_timeout(&step);
+        self.event_hub.publish(ConsensusEvent::Timeout {
+            epoch_id,
+            round: round.unwrap_or(self.round),
+            step,
+        });
+
+        self.state_machine.trigger(Context::new(), SMRTrigger {
             source: TriggerSource::Timer,
             hash: Hash::new(),
             trigger_type,
@@ -194,82 +259,122 @@ impl Timer {
     }
 }
 
-/// Timeout info which is a future consists of a `futures-timer Delay`, timeout info and a sender.
-/// When the timeout expires, future will send timeout info by sender.
-#[derive(Debug, Display)]
+/// Timeout info which is a future consists of a delay of the clock and timeout info. When the
+/// timeout expires, future will output the timeout info.
+#[derive(Display)]
 #[display(fmt = "{:?}", info)]
 struct TimeoutInfo {
-    timeout: Delay,
+    timeout: BoxFuture<'static, ()>,
     info:    SMREvent,
-    sender:  UnboundedSender<SMREvent>,
 }
 
-impl Future for TimeoutInfo {
-    type Output = ();
+impl Debug for TimeoutInfo {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        f.debug_struct("TimeoutInfo")
+            .field("info", &self.info)
+            .finish()
+    }
+}
 
-    fn poll(mut self: Pin<&mut Self>, cx: &mut Context) -> Poll<Self::Output> {
-        let msg = self.info.clone();
-        let mut tx = self.sender.clone();
+impl Future for TimeoutInfo {
+    type Output = SMREvent;
 
+    fn poll(mut self: Pin<&mut Self>, cx: &mut TaskContext) -> Poll<Self::Output> {
         match self.timeout.poll_unpin(cx) {
             Poll::Pending => Poll::Pending,
-            Poll::Ready(_) => {
-                runtime::spawn(async move {
-                    let _ = tx.send(msg).await;
-                });
-                Poll::Ready(())
-            }
+            Poll::Ready(_) => Poll::Ready(self.info.clone()),
         }
     }
 }
 
 impl TimeoutInfo {
-    fn new(interval: Duration, event: SMREvent, tx: UnboundedSender<SMREvent>) -> Self {
-        // let mut delay = Delay::new_handle(Instant::now(), TimerHandle::default());
-        // delay.reset(interval);
-
-        let delay = Delay::new(interval);
-
+    fn new(delay: BoxFuture<'static, ()>, event: SMREvent) -> Self {
         TimeoutInfo {
             timeout: delay,
             info:    event,
-            sender:  tx,
         }
     }
 }
 
 #[cfg(test)]
 mod test {
-    use futures::channel::mpsc::unbounded;
-    use futures::stream::StreamExt;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::Arc;
+    use std::time::Duration;
 
+    use creep::Context;
+    use futures::channel::mpsc::{unbounded, UnboundedReceiver, UnboundedSender};
+    use futures::executor::LocalPool;
+    use parking_lot::RwLock;
+
+    use crate::clock::VirtualClock;
     use crate::smr::smr_types::{SMREvent, SMRTrigger, TriggerSource, TriggerType};
     use crate::smr::{Event, SMRHandler};
-    use crate::{timer::Timer, types::Hash};
-
-    async fn test_timer_trigger(input: SMREvent, output: SMRTrigger) {
-        let (trigger_tx, mut trigger_rx) = unbounded();
-        let (event_tx, event_rx) = unbounded();
-        let mut timer = Timer::new(
-            Event::new(event_rx),
-            SMRHandler::new(trigger_tx),
-            3000,
-            None,
-        );
-        event_tx.unbounded_send(input).unwrap();
+    use crate::spawner::LocalPoolSpawner;
+    use crate::utils::{event_hub::EventHub, timer_config::TimerConfig};
+    use crate::{timer::Timer, types::Hash, OverlordConfig};
+
+    struct TestTimer {
+        pool:        LocalPool,
+        clock:       VirtualClock,
+        live_timers: Arc<AtomicUsize>,
+        event_tx:    UnboundedSender<(Context, SMREvent)>,
+        trigger_rx:  UnboundedReceiver<(Context, SMRTrigger)>,
+    }
 
-        runtime::spawn(async move {
-            loop {
-                match timer.next().await {
-                    None => break,
-                    Some(_) => panic!("Error"),
-                }
+    impl TestTimer {
+        fn new() -> Self {
+            let pool = LocalPool::new();
+            let clock = VirtualClock::new();
+            let live_timers = Arc::new(AtomicUsize::new(0));
+            let (trigger_tx, trigger_rx) = unbounded();
+            let (event_tx, event_rx) = unbounded();
+            let timer = Timer::new(
+                Event::new(event_rx),
+                SMRHandler::new(trigger_tx),
+                Arc::new(RwLock::new(TimerConfig::new(&OverlordConfig::default()))),
+                EventHub::new(),
+                Arc::new(LocalPoolSpawner::new(pool.spawner()).unwrap()),
+                Arc::new(clock.clone()),
+                Arc::clone(&live_timers),
+            );
+            timer.run();
+
+            TestTimer {
+                pool,
+                clock,
+                live_timers,
+                event_tx,
+                trigger_rx,
             }
-        });
+        }
+
+        fn send(&mut self, event: SMREvent) {
+            self.event_tx
+                .unbounded_send((Context::new(), event))
+                .unwrap();
+            self.pool.run_until_stalled();
+        }
+
+        fn advance(&mut self, millis: u64) -> Vec<SMRTrigger> {
+            self.clock.advance(Duration::from_millis(millis));
+            self.pool.run_until_stalled();
+
+            let mut triggers = Vec::new();
+            while let Ok(Some((_, trigger))) = self.trigger_rx.try_next() {
+                triggers.push(trigger);
+            }
+            triggers
+        }
+
+        fn live_timers(&self) -> usize {
+            self.live_timers.load(Ordering::Acquire)
+        }
 
-        if let Some(res) = trigger_rx.next().await {
-            assert_eq!(res, output);
-            event_tx.unbounded_send(SMREvent::Stop).unwrap();
+        fn stop(mut self) {
+            self.send(SMREvent::Stop);
+            assert_eq!(self.live_timers(), 0);
+            assert_eq!(self.clock.pending_delays(), 0);
         }
     }
 
@@ -283,8 +388,19 @@ mod test {
         }
     }
 
-    #[runtime::test]
-    async fn test_correctness() {
+    fn test_timer_trigger(input: SMREvent, timeout: u64, output: SMRTrigger) {
+        let mut timer = TestTimer::new();
+        timer.send(input);
+        assert_eq!(timer.live_timers(), 1);
+
+        assert!(timer.advance(timeout - 1).is_empty());
+        assert_eq!(timer.advance(1), vec![output]);
+        assert_eq!(timer.live_timers(), 0);
+        timer.stop();
+    }
+
+    #[test]
+    fn test_correctness() {
         // Test propose step timer.
         test_timer_trigger(
             SMREvent::NewRoundInfo {
@@ -293,9 +409,9 @@ mod test {
                 lock_round:    None,
                 lock_proposal: None,
             },
+            2400,
             gen_output(TriggerType::Proposal, None, 0),
-        )
-        .await;
+        );
 
         // Test prevote step timer.
         test_timer_trigger(
@@ -303,10 +419,11 @@ mod test {
                 epoch_id:   0u64,
                 round:      0u64,
                 epoch_hash: Hash::new(),
+                lock_round: None,
             },
+            1000,
             gen_output(TriggerType::PrevoteQC, Some(0), 0),
-        )
-        .await;
+        );
 
         // Test precommit step timer.
         test_timer_trigger(
@@ -314,72 +431,81 @@ mod test {
                 epoch_id:   0u64,
                 round:      0u64,
                 epoch_hash: Hash::new(),
+                lock_round: None,
             },
+            500,
             gen_output(TriggerType::PrecommitQC, Some(0), 0),
-        )
-        .await;
+        );
     }
 
-    #[runtime::test]
-    async fn test_order() {
-        let (trigger_tx, mut trigger_rx) = unbounded();
-        let (event_tx, event_rx) = unbounded();
-        let mut timer = Timer::new(
-            Event::new(event_rx),
-            SMRHandler::new(trigger_tx),
-            3000,
-            None,
-        );
+    #[test]
+    fn test_cancel_previous_steps() {
+        let mut timer = TestTimer::new();
 
-        let new_round_event = SMREvent::NewRoundInfo {
+        timer.send(SMREvent::NewRoundInfo {
             epoch_id:      0,
             round:         0,
             lock_round:    None,
             lock_proposal: None,
-        };
-
-        let prevote_event = SMREvent::PrevoteVote {
+        });
+        timer.send(SMREvent::PrevoteVote {
             epoch_id:   0u64,
             round:      0u64,
             epoch_hash: Hash::new(),
-        };
-
-        let precommit_event = SMREvent::PrecommitVote {
+            lock_round: None,
+        });
+        timer.send(SMREvent::PrecommitVote {
             epoch_id:   0u64,
             round:      0u64,
             epoch_hash: Hash::new(),
-        };
+            lock_round: None,
+        });
 
-        runtime::spawn(async move {
-            loop {
-                match timer.next().await {
-                    None => break,
-                    Some(_) => panic!("Error"),
-                }
-            }
+        // The propose and prevote timeouts are cancelled as the step advances.
+        assert_eq!(timer.live_timers(), 1);
+        assert_eq!(timer.clock.pending_delays(), 1);
+
+        let mut output = timer.advance(500);
+        output.append(&mut timer.advance(500));
+        output.append(&mut timer.advance(1400));
+
+        let predict = vec![gen_output(TriggerType::PrecommitQC, Some(0), 0)];
+        assert_eq!(predict, output);
+        timer.stop();
+    }
+
+    #[test]
+    fn test_cancel_on_new_round_and_commit() {
+        let mut timer = TestTimer::new();
+
+        timer.send(SMREvent::PrecommitVote {
+            epoch_id:   0u64,
+            round:      0u64,
+            epoch_hash: Hash::new(),
+            lock_round: None,
         });
+        timer.send(SMREvent::NewRoundInfo {
+            epoch_id:      0,
+            round:         1,
+            lock_round:    None,
+            lock_proposal: None,
+        });
+        assert_eq!(timer.live_timers(), 1);
 
-        event_tx.unbounded_send(new_round_event).unwrap();
-        event_tx.unbounded_send(prevote_event).unwrap();
-        event_tx.unbounded_send(precommit_event).unwrap();
+        // Only the propose timeout of the new round expires, which is doubled by the backoff.
+        let predict = vec![gen_output(TriggerType::Proposal, None, 0)];
+        assert!(timer.advance(4799).is_empty());
+        assert_eq!(timer.advance(1), predict);
 
-        let mut count = 1u32;
-        let mut output = Vec::new();
-        let predict = vec![
-            gen_output(TriggerType::PrecommitQC, Some(0), 0),
-            gen_output(TriggerType::PrevoteQC, Some(0), 0),
-            gen_output(TriggerType::Proposal, None, 0),
-        ];
-
-        while let Some(res) = trigger_rx.next().await {
-            output.push(res);
-            if count != 3 {
-                count += 1;
-            } else {
-                assert_eq!(predict, output);
-                event_tx.unbounded_send(SMREvent::Stop).unwrap();
-                return;
-            }
-        }
+        timer.send(SMREvent::PrevoteVote {
+            epoch_id:   0u64,
+            round:      1u64,
+            epoch_hash: Hash::new(),
+            lock_round: None,
+        });
+        timer.send(SMREvent::Commit(Hash::new()));
+        assert_eq!(timer.live_timers(), 0);
+        assert!(timer.advance(3000).is_empty());
+        timer.stop();
     }
 }
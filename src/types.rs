@@ -1,11 +1,12 @@
 use std::cmp::{Ord, Ordering, PartialOrd};
+use std::time::Duration;
 
 use bytes::Bytes;
 use derive_more::Display;
 use serde::{Deserialize, Serialize};
 
-use crate::smr::smr_types::TriggerType;
-use crate::Codec;
+use crate::smr::smr_types::{Lock, Step, TriggerType};
+use crate::{error::ConsensusError, Codec, ConsensusResult, DurationConfig};
 
 /// Address type.
 pub type Address = Bytes;
@@ -103,6 +104,10 @@ pub enum OverlordMsg<T: Codec> {
     /// Rich status message.
     #[display(fmt = "Rich Status")]
     RichStatus(Status),
+    /// Stop the overlord instance. The SMR, timer and state processes will be stopped, then the
+    /// `run` function returns.
+    #[display(fmt = "Stop")]
+    Stop,
 
     /// This is only for easier testing.
     #[cfg(test)]
@@ -272,10 +277,184 @@ pub struct Status {
     pub epoch_id: u64,
     /// New block interval.
     pub interval: Option<u64>,
+    /// New timeout ratios of each step.
+    pub timer_config: Option<DurationConfig>,
     /// New authority list.
     pub authority_list: Vec<Node>,
 }
 
+/// A trusted checkpoint to start the consensus from, such as a snapshot that the node restored.
+/// The consensus enters the checkpoint epoch directly, unless the Wal has a higher epoch.
+#[derive(Clone, Debug, Display, PartialEq, Eq)]
+#[display(fmt = "Checkpoint epoch ID {}", epoch_id)]
+pub struct Checkpoint {
+    /// Epoch ID to start the consensus from.
+    pub epoch_id: u64,
+    /// Authority list of the checkpoint epoch.
+    pub authority_list: Vec<Node>,
+    /// Authority list of the previous epoch, which is used to handle the messages of the previous
+    /// epoch. It can be empty at the first epoch.
+    pub last_authority_list: Vec<Node>,
+    /// The commit proof of the previous epoch, if any. It is used to re-transmit the votes to the
+    /// nodes that are behind.
+    pub last_proof: Option<Proof>,
+}
+
+impl Checkpoint {
+    /// Validate the checkpoint. Return `Err(ConsensusError::ConfigErr)` if the authority list is
+    /// empty, or the last proof does not belong to the previous epoch.
+    pub fn validate(&self) -> ConsensusResult<()> {
+        if self.authority_list.is_empty() {
+            return Err(ConsensusError::ConfigErr(
+                "checkpoint authority list must not be empty".to_string(),
+            ));
+        }
+
+        if let Some(proof) = self.last_proof.as_ref() {
+            if proof.epoch_id + 1 != self.epoch_id {
+                return Err(ConsensusError::ConfigErr(format!(
+                    "checkpoint epoch ID {} mismatches the last proof epoch ID {}",
+                    self.epoch_id, proof.epoch_id
+                )));
+            }
+        }
+        Ok(())
+    }
+}
+
+/// A snapshot of the consensus status, which is the response of
+/// `OverlordHandler::query_status()`.
+#[derive(Clone, Debug, Default, Display, PartialEq, Eq)]
+#[rustfmt::skip]
+#[display(fmt = "Consensus status epoch ID {}, round {}, step {:?}", epoch_id, round, step)]
+pub struct ConsensusStatus {
+    /// Current epoch ID.
+    pub epoch_id: u64,
+    /// Current round.
+    pub round: u64,
+    /// Current step of the SMR.
+    pub step: Step,
+    /// Lock of the SMR, if any.
+    pub lock: Option<Lock>,
+    /// Leader address of the current round.
+    pub leader: Address,
+    /// Whether self is the leader of the current round.
+    pub is_leader: bool,
+    /// Authority list of the current epoch.
+    pub authority_list: Vec<Node>,
+    /// How long the current epoch has run.
+    pub epoch_duration: Duration,
+}
+
+/// Consensus lifecycle events, which can be subscribed by `OverlordHandler::subscribe()`.
+#[derive(Clone, Debug, Display, PartialEq, Eq)]
+pub enum ConsensusEvent {
+    /// Goto a new epoch.
+    #[display(fmt = "New epoch {}", epoch_id)]
+    NewEpoch {
+        /// Epoch ID of the new epoch.
+        epoch_id: u64,
+    },
+    /// Goto a new round.
+    #[display(fmt = "New round epoch ID {}, round {}", epoch_id, round)]
+    NewRound {
+        /// Epoch ID of the round.
+        epoch_id: u64,
+        /// The new round.
+        round: u64,
+    },
+    /// Receive a valid proposal of the current epoch ID and round from the leader.
+    #[display(fmt = "Proposal received epoch ID {}, round {}", epoch_id, round)]
+    ProposalReceived {
+        /// Epoch ID of the proposal.
+        epoch_id: u64,
+        /// Round of the proposal.
+        round: u64,
+        /// Epoch hash of the proposal.
+        epoch_hash: Hash,
+        /// Proposer address.
+        proposer: Address,
+    },
+    /// Self as the leader sends a proposal.
+    #[display(fmt = "Proposal sent epoch ID {}, round {}", epoch_id, round)]
+    ProposalSent {
+        /// Epoch ID of the proposal.
+        epoch_id: u64,
+        /// Round of the proposal.
+        round: u64,
+        /// Epoch hash of the proposal.
+        epoch_hash: Hash,
+    },
+    /// A quorum certificate of the current epoch ID and round is formed, which is aggregated by
+    /// self or received from the leader.
+    #[rustfmt::skip]
+    #[display(fmt = "{:?} QC formed epoch ID {}, round {}", vote_type, epoch_id, round)]
+    QCFormed {
+        /// Epoch ID of the QC.
+        epoch_id: u64,
+        /// Round of the QC.
+        round: u64,
+        /// Type of the QC.
+        vote_type: VoteType,
+        /// Epoch hash of the QC.
+        epoch_hash: Hash,
+    },
+    /// A step timer fires.
+    #[rustfmt::skip]
+    #[display(fmt = "Timeout epoch ID {}, round {}, step {:?}", epoch_id, round, step)]
+    Timeout {
+        /// Epoch ID of the timer.
+        epoch_id: u64,
+        /// Round of the timer.
+        round: u64,
+        /// The step that times out.
+        step: Step,
+    },
+    /// An epoch is committed.
+    #[display(fmt = "Commit epoch ID {}, round {}", epoch_id, round)]
+    Commit {
+        /// Epoch ID of the commit.
+        epoch_id: u64,
+        /// Round of the commit.
+        round: u64,
+        /// Epoch hash of the commit.
+        epoch_hash: Hash,
+    },
+    /// Fail to commit an epoch after all the retries. The application should resync and send the
+    /// rich status of the next epoch to resume the consensus.
+    #[display(fmt = "Commit failed epoch ID {}, round {}", epoch_id, round)]
+    CommitFailed {
+        /// Epoch ID of the commit.
+        epoch_id: u64,
+        /// Round of the commit.
+        round: u64,
+        /// How many times the commit has been tried.
+        times: u32,
+    },
+    /// The node stays in the commit step longer than the commit timeout, since the commit
+    /// callback hangs or the rich status of the next epoch never arrives. It is published
+    /// repeatedly until the node goes to the next epoch.
+    #[display(fmt = "Commit stalled epoch ID {}, round {}", epoch_id, round)]
+    CommitStalled {
+        /// Epoch ID of the commit.
+        epoch_id: u64,
+        /// Round of the commit.
+        round: u64,
+        /// How long the node has stayed in the commit step.
+        duration: Duration,
+    },
+    /// The lock of the SMR changes.
+    #[display(fmt = "Lock changed epoch ID {}, round {}", epoch_id, round)]
+    LockChanged {
+        /// Epoch ID of the lock.
+        epoch_id: u64,
+        /// Round when the lock changes.
+        round: u64,
+        /// The new lock, `None` means unlocked.
+        lock: Option<Lock>,
+    },
+}
+
 /// A node info.
 #[derive(Clone, Debug, PartialEq, Eq)]
 pub struct Node {
@@ -333,9 +512,26 @@ pub(crate) struct Feed<T: Codec> {
 
 /// A verify response.
 #[derive(Clone, Debug, PartialEq, Eq)]
-pub(crate) struct VerifyResp {
+pub struct VerifyResp {
+    /// Epoch ID of the verified proposal.
+    pub epoch_id: u64,
     /// Verified proposal hash.
-    pub(crate) epoch_hash: Hash,
+    pub epoch_hash: Hash,
     /// The verify result.
-    pub(crate) is_pass: bool,
+    pub is_pass: bool,
+}
+
+/// The last signed message of the node, which is used to prevent double signing. The position of a
+/// signed message is ordered by epoch ID, round and step.
+#[derive(Clone, Debug, Display, PartialEq, Eq)]
+#[display(fmt = "{} of epoch ID {}, round {}", step, epoch_id, round)]
+pub struct LastSigned {
+    /// Epoch ID of the signed message.
+    pub epoch_id: u64,
+    /// Round of the signed message.
+    pub round: u64,
+    /// The propose step for a proposal, the prevote or precommit step for a vote.
+    pub step: Step,
+    /// Epoch hash of the signed message.
+    pub epoch_hash: Hash,
 }
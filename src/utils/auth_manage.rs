@@ -119,6 +119,11 @@ impl AuthorityManage {
     pub fn get_addres_ref(&self) -> &Vec<Address> {
         &self.current.address
     }
+
+    /// Get the current authority list.
+    pub fn get_authority_list(&self) -> Vec<Node> {
+        self.current.get_authority_list()
+    }
 }
 
 /// Epoch authority manage is an extensional data structure of authority list which means
@@ -165,6 +170,19 @@ impl EpochAuthorityManage {
         }
     }
 
+    /// Get the authority list which is sorted by address.
+    fn get_authority_list(&self) -> Vec<Node> {
+        self.address
+            .iter()
+            .zip(self.propose_weights.iter())
+            .map(|(address, propose_weight)| Node {
+                address:        address.clone(),
+                propose_weight: *propose_weight as u8,
+                vote_weight:    self.vote_weight_map[address],
+            })
+            .collect::<Vec<_>>()
+    }
+
     /// Get a vote weight of the node.
     fn get_vote_weight(&self, addr: &Address) -> ConsensusResult<&u8> {
         self.vote_weight_map
@@ -295,6 +313,16 @@ mod test {
         }
     }
 
+    #[test]
+    fn test_authority_list() {
+        let mut authority_list = gen_auth_list(random::<u8>() as usize);
+        let mut auth_manage = AuthorityManage::new();
+        auth_manage.update(&mut authority_list.clone(), false);
+
+        authority_list.sort();
+        assert_eq!(auth_manage.get_authority_list(), authority_list);
+    }
+
     #[test]
     fn test_update() {
         let mut authority_list = gen_auth_list(random::<u8>() as usize);
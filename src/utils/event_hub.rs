@@ -0,0 +1,100 @@
+use std::sync::Arc;
+
+use futures::channel::mpsc::{channel, Receiver, Sender};
+use log::warn;
+use parking_lot::Mutex;
+
+use crate::types::ConsensusEvent;
+
+/// The capacity of the event queue of each subscriber.
+const EVENT_CAPACITY: usize = 1024;
+
+/// An event hub publishes consensus events to all the subscribers. It is shared by the state and
+/// the timer. Each subscriber has a bounded queue, and the events are dropped for the subscriber
+/// whose queue is full, so that a slow subscriber can not grow the memory without bound. The
+/// subscribers whose receiver has been dropped are removed on publishing.
+#[derive(Clone, Debug, Default)]
+pub struct EventHub(Arc<Mutex<Vec<Sender<ConsensusEvent>>>>);
+
+impl EventHub {
+    /// Create a new event hub without subscribers.
+    pub fn new() -> Self {
+        EventHub(Arc::new(Mutex::new(Vec::new())))
+    }
+
+    /// Subscribe consensus events and return the receiver.
+    pub fn subscribe(&self) -> Receiver<ConsensusEvent> {
+        let (tx, rx) = channel(EVENT_CAPACITY);
+        self.0.lock().push(tx);
+        rx
+    }
+
+    /// Publish an event to all the subscribers.
+    pub fn publish(&self, event: ConsensusEvent) {
+        let mut subscribers = self.0.lock();
+        let remains = subscribers
+            .drain(..)
+            .filter_map(|mut tx| match tx.try_send(event.clone()) {
+                Ok(_) => Some(tx),
+                Err(ref err) if err.is_full() => {
+                    warn!("Overlord: event hub drop {:?} of a full subscriber", event);
+                    Some(tx)
+                }
+                Err(_) => None,
+            })
+            .collect::<Vec<_>>();
+        *subscribers = remains;
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use futures::StreamExt;
+
+    use crate::types::ConsensusEvent;
+    use crate::utils::event_hub::{EventHub, EVENT_CAPACITY};
+
+    #[runtime::test]
+    async fn test_event_hub() {
+        let hub = EventHub::new();
+        // Publish without subscribers does nothing.
+        hub.publish(ConsensusEvent::NewEpoch { epoch_id: 1 });
+
+        let mut rx_1 = hub.subscribe();
+        let rx_2 = hub.subscribe();
+        drop(rx_2);
+
+        let event = ConsensusEvent::NewRound {
+            epoch_id: 1,
+            round:    1,
+        };
+        hub.publish(event.clone());
+        assert_eq!(rx_1.next().await, Some(event));
+        assert_eq!(hub.0.lock().len(), 1);
+    }
+
+    #[runtime::test]
+    async fn test_full_subscriber() {
+        let hub = EventHub::new();
+        let mut rx = hub.subscribe();
+
+        // The events are dropped when the queue of the subscriber is full, and the subscriber is
+        // kept.
+        for epoch_id in 0..2 * EVENT_CAPACITY as u64 {
+            hub.publish(ConsensusEvent::NewEpoch { epoch_id });
+        }
+        assert_eq!(hub.0.lock().len(), 1);
+
+        let mut count = 0u64;
+        while let Ok(Some(event)) = rx.try_next() {
+            assert_eq!(event, ConsensusEvent::NewEpoch { epoch_id: count });
+            count += 1;
+        }
+        assert!(count < 2 * EVENT_CAPACITY as u64);
+
+        // The subscriber receives events again after the queue is consumed.
+        let event = ConsensusEvent::NewEpoch { epoch_id: 0 };
+        hub.publish(event.clone());
+        assert_eq!(rx.next().await, Some(event));
+    }
+}
@@ -1,6 +1,8 @@
 ///
 pub mod auth_manage;
 ///
+pub mod event_hub;
+///
 mod rand_proposer;
 ///
 pub mod timer_config;
@@ -1,26 +1,51 @@
-use std::{cell::Cell, time::Duration};
+use std::{sync::Arc, time::Duration};
 
-use crate::smr::smr_types::SMREvent;
-use crate::DurationConfig;
+use crate::backoff::BackoffPolicy;
+use crate::smr::smr_types::{SMREvent, Step};
 use crate::{error::ConsensusError, ConsensusResult};
+use crate::{AdaptiveTimeoutConfig, DurationConfig, OverlordConfig};
 
-/// Overlord timer config.
+/// Overlord timer config. It is shared by the state and the timer, so that the state can update
+/// the interval and the timeout ratios at the epoch boundary.
 #[derive(Debug, Clone)]
 pub struct TimerConfig {
-    interval:  Cell<u64>,
-    propose:   (u64, u64),
-    prevote:   (u64, u64),
-    precommit: (u64, u64),
+    interval:          u64,
+    propose:           (u64, u64),
+    prevote:           (u64, u64),
+    precommit:         (u64, u64),
+    propose_backoff:   Arc<dyn BackoffPolicy>,
+    prevote_backoff:   Arc<dyn BackoffPolicy>,
+    precommit_backoff: Arc<dyn BackoffPolicy>,
+    adaptive:          Option<AdaptiveTimeoutConfig>,
+    propose_latency:   LatencyEstimator,
+    prevote_latency:   LatencyEstimator,
+    precommit_latency: LatencyEstimator,
 }
 
 impl TimerConfig {
-    pub fn new(interval: u64) -> Self {
-        TimerConfig {
-            interval:  Cell::new(interval),
-            propose:   (24, 30),
-            prevote:   (10, 30),
-            precommit: (5, 30),
+    pub fn new(config: &OverlordConfig) -> Self {
+        let mut timer_config = TimerConfig {
+            interval:          config.interval,
+            propose:           (24, 30),
+            prevote:           (10, 30),
+            precommit:         (5, 30),
+            propose_backoff:   Arc::from(config.propose_backoff.build()),
+            prevote_backoff:   Arc::from(config.prevote_backoff.build()),
+            precommit_backoff: Arc::from(config.precommit_backoff.build()),
+            adaptive:          config.adaptive_timeout.clone(),
+            propose_latency:   LatencyEstimator::default(),
+            prevote_latency:   LatencyEstimator::default(),
+            precommit_latency: LatencyEstimator::default(),
+        };
+
+        if let Some(duration) = config.duration.clone() {
+            timer_config.update(duration);
         }
+        timer_config
+    }
+
+    pub fn set_interval(&mut self, interval: u64) {
+        self.interval = interval;
     }
 
     pub fn update(&mut self, config: DurationConfig) {
@@ -29,24 +54,239 @@ impl TimerConfig {
         self.precommit = config.get_precommit_config();
     }
 
-    pub fn get_timeout(&self, event: SMREvent) -> ConsensusResult<Duration> {
-        match event {
-            SMREvent::NewRoundInfo { .. } => Ok(self.get_propose_timeout()),
-            SMREvent::PrevoteVote { .. } => Ok(self.get_prevote_timeout()),
-            SMREvent::PrecommitVote { .. } => Ok(self.get_precommit_timeout()),
-            _ => Err(ConsensusError::TimerErr("No commit timer".to_string())),
+    /// Observe the latency of a step, which is the time from entering the step to receiving the
+    /// proposal or the quorum certificate. It is ignored if the adaptive timeout is disabled.
+    pub fn observe(&mut self, step: &Step, latency: Duration) {
+        if self.adaptive.is_none() {
+            return;
+        }
+
+        let latency = latency.as_millis() as u64;
+        match step {
+            Step::Propose => self.propose_latency.observe(latency),
+            Step::Prevote => self.prevote_latency.observe(latency),
+            Step::Precommit => self.precommit_latency.observe(latency),
+            Step::Commit => (),
         }
     }
 
+    /// Inflate the latency estimate of a step that times out, since no latency is observed when
+    /// the proposal or the quorum certificate does not arrive in time. It is ignored if the
+    /// adaptive timeout is disabled.
+    pub fn observe_timeout(&mut self, step: &Step) {
+        if self.adaptive.is_none() {
+            return;
+        }
+
+        match step {
+            Step::Propose => self.propose_latency.expire(),
+            Step::Prevote => self.prevote_latency.expire(),
+            Step::Precommit => self.precommit_latency.expire(),
+            Step::Commit => (),
+        }
+    }
+
+    /// Replace the backoff policy of the step. The commit step has no timer, so it is ignored.
+    pub fn set_backoff(&mut self, step: &Step, policy: Arc<dyn BackoffPolicy>) {
+        match step {
+            Step::Propose => self.propose_backoff = policy,
+            Step::Prevote => self.prevote_backoff = policy,
+            Step::Precommit => self.precommit_backoff = policy,
+            Step::Commit => (),
+        }
+    }
+
+    /// Get the timeout of the event. The base timeout of the step is extended by the backoff
+    /// policy of the step as the round increasing.
+    pub fn get_timeout(&self, event: SMREvent) -> ConsensusResult<Duration> {
+        let (base, policy, round) = match event {
+            SMREvent::NewRoundInfo { round, .. } => {
+                (self.get_propose_timeout(), &self.propose_backoff, round)
+            }
+            SMREvent::PrevoteVote { round, .. } => {
+                (self.get_prevote_timeout(), &self.prevote_backoff, round)
+            }
+            SMREvent::PrecommitVote { round, .. } => {
+                (self.get_precommit_timeout(), &self.precommit_backoff, round)
+            }
+            _ => return Err(ConsensusError::TimerErr("No commit timer".to_string())),
+        };
+        Ok(policy.backoff(base, round))
+    }
+
     fn get_propose_timeout(&self) -> Duration {
-        Duration::from_millis(self.interval.get() * self.propose.0 / self.propose.1)
+        self.adaptive_timeout(&self.propose_latency)
+            .unwrap_or_else(|| {
+                Duration::from_millis(self.interval * self.propose.0 / self.propose.1)
+            })
     }
 
     fn get_prevote_timeout(&self) -> Duration {
-        Duration::from_millis(self.interval.get() * self.prevote.0 / self.prevote.1)
+        self.adaptive_timeout(&self.prevote_latency)
+            .unwrap_or_else(|| {
+                Duration::from_millis(self.interval * self.prevote.0 / self.prevote.1)
+            })
     }
 
     fn get_precommit_timeout(&self) -> Duration {
-        Duration::from_millis(self.interval.get() * self.precommit.0 / self.precommit.1)
+        self.adaptive_timeout(&self.precommit_latency)
+            .unwrap_or_else(|| {
+                Duration::from_millis(self.interval * self.precommit.0 / self.precommit.1)
+            })
+    }
+
+    /// Get the adaptive timeout from the latency estimate within the bounds. Return `None` if the
+    /// adaptive timeout is disabled or no latency has been observed.
+    fn adaptive_timeout(&self, latency: &LatencyEstimator) -> Option<Duration> {
+        let adaptive = self.adaptive.as_ref()?;
+        let timeout = latency
+            .timeout()?
+            .max(adaptive.min_timeout)
+            .min(adaptive.max_timeout);
+        Some(Duration::from_millis(timeout))
+    }
+}
+
+/// A moving estimate of a step latency as millisecond, which works like the TCP retransmission
+/// timeout estimation. The timeout is the smoothed latency plus four times the deviation, and it
+/// is doubled on each timeout until a latency is observed again.
+#[derive(Clone, Debug, Default)]
+struct LatencyEstimator {
+    smoothed:  Option<u64>,
+    deviation: u64,
+    expired:   u32,
+}
+
+impl LatencyEstimator {
+    fn observe(&mut self, latency: u64) {
+        self.expired = 0;
+        if let Some(smoothed) = self.smoothed {
+            let diff = if smoothed > latency {
+                smoothed - latency
+            } else {
+                latency - smoothed
+            };
+            self.deviation = (self.deviation * 3 + diff) / 4;
+            self.smoothed = Some((smoothed * 7 + latency) / 8);
+        } else {
+            self.deviation = latency / 2;
+            self.smoothed = Some(latency);
+        }
+    }
+
+    fn expire(&mut self) {
+        // The timeout is capped by the upper bound long before the shift overflows.
+        if self.smoothed.is_some() && self.expired < 63 {
+            self.expired += 1;
+        }
+    }
+
+    fn timeout(&self) -> Option<u64> {
+        self.smoothed.map(|smoothed| {
+            smoothed
+                .saturating_add(self.deviation.saturating_mul(4))
+                .saturating_mul(1 << self.expired)
+        })
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::time::Duration;
+
+    use crate::smr::smr_types::{SMREvent, Step};
+    use crate::utils::timer_config::TimerConfig;
+    use crate::{types::Hash, AdaptiveTimeoutConfig, OverlordConfig};
+
+    fn prevote_event() -> SMREvent {
+        SMREvent::PrevoteVote {
+            epoch_id:   1,
+            round:      0,
+            epoch_hash: Hash::new(),
+            lock_round: None,
+        }
+    }
+
+    #[test]
+    fn test_adaptive_timeout() {
+        let mut config = OverlordConfig::default();
+        let mut timer_config = TimerConfig::new(&config);
+        timer_config.observe(&Step::Prevote, Duration::from_millis(100));
+        assert_eq!(
+            timer_config.get_timeout(prevote_event()).unwrap(),
+            Duration::from_millis(1000)
+        );
+
+        config.adaptive_timeout = Some(AdaptiveTimeoutConfig::new(50, 2000));
+        let mut timer_config = TimerConfig::new(&config);
+        assert_eq!(
+            timer_config.get_timeout(prevote_event()).unwrap(),
+            Duration::from_millis(1000)
+        );
+
+        // The first latency 100ms makes the timeout 100 + 4 * 50.
+        timer_config.observe(&Step::Prevote, Duration::from_millis(100));
+        assert_eq!(
+            timer_config.get_timeout(prevote_event()).unwrap(),
+            Duration::from_millis(300)
+        );
+
+        // The stable latency makes the timeout converge to the latency, but not lower than the
+        // lower bound.
+        for _ in 0..100 {
+            timer_config.observe(&Step::Prevote, Duration::from_millis(20));
+        }
+        assert_eq!(
+            timer_config.get_timeout(prevote_event()).unwrap(),
+            Duration::from_millis(50)
+        );
+
+        // A huge latency is capped at the upper bound.
+        timer_config.observe(&Step::Prevote, Duration::from_millis(100_000));
+        assert_eq!(
+            timer_config.get_timeout(prevote_event()).unwrap(),
+            Duration::from_millis(2000)
+        );
+    }
+
+    #[test]
+    fn test_adaptive_timeout_expire() {
+        let mut config = OverlordConfig::default();
+        config.adaptive_timeout = Some(AdaptiveTimeoutConfig::new(50, 2000));
+        let mut timer_config = TimerConfig::new(&config);
+
+        // A timeout before any latency is observed keeps the default timeout.
+        timer_config.observe_timeout(&Step::Prevote);
+        assert_eq!(
+            timer_config.get_timeout(prevote_event()).unwrap(),
+            Duration::from_millis(1000)
+        );
+
+        // Each timeout doubles the timeout up to the upper bound.
+        timer_config.observe(&Step::Prevote, Duration::from_millis(100));
+        timer_config.observe_timeout(&Step::Prevote);
+        assert_eq!(
+            timer_config.get_timeout(prevote_event()).unwrap(),
+            Duration::from_millis(600)
+        );
+        timer_config.observe_timeout(&Step::Prevote);
+        assert_eq!(
+            timer_config.get_timeout(prevote_event()).unwrap(),
+            Duration::from_millis(1200)
+        );
+        for _ in 0..100 {
+            timer_config.observe_timeout(&Step::Prevote);
+        }
+        assert_eq!(
+            timer_config.get_timeout(prevote_event()).unwrap(),
+            Duration::from_millis(2000)
+        );
+
+        // An observed latency resets the doubling, the timeout is 100 + 4 * 37.
+        timer_config.observe(&Step::Prevote, Duration::from_millis(100));
+        assert_eq!(
+            timer_config.get_timeout(prevote_event()).unwrap(),
+            Duration::from_millis(248)
+        );
     }
 }
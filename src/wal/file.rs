@@ -0,0 +1,659 @@
+use std::error::Error;
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+
+use async_trait::async_trait;
+use bytes::Bytes;
+use log::{debug, warn};
+use parking_lot::Mutex;
+
+use crate::{error::ConsensusError, WalStorage};
+
+const SEGMENT_EXTENSION: &str = "wal";
+const LENGTH_SIZE: usize = 4;
+const CHECKSUM_SIZE: usize = 4;
+const HEADER_SIZE: usize = LENGTH_SIZE + CHECKSUM_SIZE;
+
+/// An append-only file storage of the write ahead log. The records of each epoch are appended to
+/// a segment file named `{epoch_id}.wal` in the given directory, so the segment rotates as going
+/// to a new epoch. Each frame consists of the record length, a CRC32 checksum of the record and
+/// the record.
+///
+/// Flushing removes the segments that epoch ID is lower than `till - retention`, the `retention`
+/// is the number of outdated epochs that will be kept for inspection.
+///
+/// Only the last frame of the last segment can be torn by a partial write. Loading is read-only,
+/// it skips the torn frame and reports its offset, while any other invalid frame returns a
+/// `ConsensusError::WalCorrupted` error. The torn frame is truncated before the storage writes to
+/// the last segment again.
+#[derive(Debug)]
+pub struct FileWalStorage {
+    path:      PathBuf,
+    retention: u64,
+    current:   Mutex<Option<(u64, File)>>,
+}
+
+/// A frame of the file storage with its position, see `FileWalStorage::load_frames`.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct WalFrame {
+    /// The epoch ID of the segment.
+    pub epoch_id: u64,
+    /// The offset of the frame in the segment.
+    pub offset: u64,
+    /// The record of the frame, or the reason why the frame is invalid. The frames after an
+    /// invalid one in the same segment can not be located, so they are not loaded.
+    pub record: Result<Bytes, String>,
+}
+
+impl FileWalStorage {
+    /// Create a new file storage with the directory path and the retention window.
+    pub fn new(path: &str, retention: u64) -> Self {
+        FileWalStorage {
+            path: PathBuf::from(path),
+            retention,
+            current: Mutex::new(None),
+        }
+    }
+
+    fn append_frame(
+        &self,
+        current: &mut Option<(u64, File)>,
+        epoch_id: u64,
+        record: &[u8],
+    ) -> io::Result<()> {
+        let is_rotate = match current {
+            Some((current_epoch, _)) => *current_epoch != epoch_id,
+            None => true,
+        };
+
+        if current.is_none() {
+            self.repair_last_segment()?;
+        }
+
+        if is_rotate {
+            debug!("Overlord: Wal rotate to segment of epoch {}", epoch_id);
+            fs::create_dir_all(&self.path)?;
+            let file = OpenOptions::new()
+                .create(true)
+                .append(true)
+                .open(self.segment_path(epoch_id))?;
+            *current = Some((epoch_id, file));
+        }
+
+        let (_, file) = current.as_mut().unwrap();
+        let res = file
+            .write_all(&encode_frame(record))
+            .and_then(|_| file.sync_data());
+        if res.is_err() {
+            // The frame may be partially written, repair the segment before the next append.
+            *current = None;
+        }
+        res
+    }
+
+    /// Truncate the torn frame at the tail of the last segment, if any. This is the only segment
+    /// that a partial write can tear, and it must be repaired before appending to the Wal.
+    fn repair_last_segment(&self) -> io::Result<()> {
+        let (_, path) = match self.segments()?.pop() {
+            Some(segment) => segment,
+            None => return Ok(()),
+        };
+
+        let data = fs::read(&path)?;
+        if let (_, Some(FrameError::Torn(offset))) = decode_frames(&data, true) {
+            warn!(
+                "Overlord: Wal truncate a torn record at offset {} in segment {:?}",
+                offset, path
+            );
+            set_segment_len(&path, offset)?;
+        }
+        Ok(())
+    }
+
+    /// Load all frames with their positions without modifying the segments. Unlike `load`, an
+    /// invalid frame is loaded with the reason instead of returning an error, so that the Wal can
+    /// be inspected and repaired offline.
+    pub fn load_frames(&self) -> Result<Vec<WalFrame>, Box<dyn Error + Send>> {
+        let _current = self.current.lock();
+        let segments = self.segments().map_err(io_err)?;
+        let last = segments.len().saturating_sub(1);
+        let mut frames = Vec::new();
+
+        for (index, (epoch_id, path)) in segments.into_iter().enumerate() {
+            let data = fs::read(&path).map_err(io_err)?;
+            let (valid, err) = decode_frames(&data, index == last);
+            frames.extend(valid.into_iter().map(|(offset, record)| WalFrame {
+                epoch_id,
+                offset: offset as u64,
+                record: Ok(record),
+            }));
+
+            let invalid = match err {
+                Some(FrameError::Torn(offset)) => (offset, "torn record"),
+                Some(FrameError::Corrupted(offset, reason)) => (offset, reason),
+                None => continue,
+            };
+            frames.push(WalFrame {
+                epoch_id,
+                offset: invalid.0 as u64,
+                record: Err(invalid.1.to_string()),
+            });
+        }
+        Ok(frames)
+    }
+
+    /// Remove the frame at the offset of the segment of the given epoch ID, and all the frames
+    /// after it. This is used to repair the Wal offline.
+    pub fn truncate(&self, epoch_id: u64, offset: u64) -> Result<(), Box<dyn Error + Send>> {
+        let mut current = self.current.lock();
+        *current = None;
+
+        for (segment_epoch, path) in self.segments().map_err(io_err)?.into_iter() {
+            if segment_epoch < epoch_id {
+                continue;
+            }
+
+            if segment_epoch == epoch_id && offset > 0 {
+                let len = fs::metadata(&path).map_err(io_err)?.len();
+                if offset > len {
+                    return Err(io_err(io::Error::new(
+                        io::ErrorKind::InvalidInput,
+                        format!("offset {} is beyond segment {:?}", offset, path),
+                    )));
+                }
+                set_segment_len(&path, offset as usize).map_err(io_err)?;
+            } else {
+                fs::remove_file(&path).map_err(io_err)?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Read all frames from the segments in the order of epoch ID.
+    fn read_frames(&self) -> Result<Vec<Bytes>, Box<dyn Error + Send>> {
+        let mut frames = Vec::new();
+        let segments = self.segments().map_err(io_err)?;
+        let last = segments.len().saturating_sub(1);
+        for (index, (_, path)) in segments.into_iter().enumerate() {
+            let segment = self.read_segment(&path, index == last)?;
+            frames.extend(segment.into_iter().map(|(_, frame)| frame));
+        }
+        Ok(frames)
+    }
+
+    /// Read all frames with their offsets from a segment without modifying it. A torn frame at
+    /// the tail of the last segment is skipped, and any other invalid frame is an error.
+    fn read_segment(
+        &self,
+        path: &Path,
+        is_last: bool,
+    ) -> Result<Vec<(usize, Bytes)>, Box<dyn Error + Send>> {
+        let data = fs::read(path).map_err(io_err)?;
+        let (frames, err) = decode_frames(&data, is_last);
+
+        match err {
+            Some(FrameError::Torn(offset)) => warn!(
+                "Overlord: Wal skip a torn record at offset {} in segment {:?}",
+                offset, path
+            ),
+            Some(FrameError::Corrupted(offset, reason)) => {
+                return Err(wal_err(ConsensusError::WalCorrupted {
+                    offset: offset as u64,
+                    reason: format!("{} in segment {:?}", reason, path),
+                }));
+            }
+            None => (),
+        }
+        Ok(frames)
+    }
+
+    /// Get all segments sorted by epoch ID.
+    fn segments(&self) -> io::Result<Vec<(u64, PathBuf)>> {
+        if !self.path.exists() {
+            return Ok(Vec::new());
+        }
+
+        let mut segments = Vec::new();
+        for entry in fs::read_dir(&self.path)? {
+            let path = entry?.path();
+            if let Some(epoch_id) = parse_segment(&path) {
+                segments.push((epoch_id, path));
+            }
+        }
+        segments.sort();
+        Ok(segments)
+    }
+
+    fn segment_path(&self, epoch_id: u64) -> PathBuf {
+        self.path
+            .join(format!("{}.{}", epoch_id, SEGMENT_EXTENSION))
+    }
+}
+
+#[async_trait]
+impl WalStorage for FileWalStorage {
+    async fn append(&self, epoch_id: u64, record: Bytes) -> Result<(), Box<dyn Error + Send>> {
+        let mut current = self.current.lock();
+        self.append_frame(&mut current, epoch_id, &record)
+            .map_err(io_err)
+    }
+
+    async fn load(&self) -> Result<Vec<Bytes>, Box<dyn Error + Send>> {
+        let _current = self.current.lock();
+        self.read_frames()
+    }
+
+    async fn flush(&self, till: u64) -> Result<(), Box<dyn Error + Send>> {
+        let mut current = self.current.lock();
+        let till = till.saturating_sub(self.retention);
+
+        for (epoch_id, path) in self.segments().map_err(io_err)?.into_iter() {
+            if epoch_id >= till {
+                break;
+            }
+
+            debug!("Overlord: Wal remove segment of epoch {}", epoch_id);
+            if let Some((current_epoch, _)) = current.as_ref() {
+                if *current_epoch == epoch_id {
+                    *current = None;
+                }
+            }
+            fs::remove_file(&path).map_err(io_err)?;
+        }
+        Ok(())
+    }
+}
+
+fn set_segment_len(path: &Path, len: usize) -> io::Result<()> {
+    let file = OpenOptions::new().write(true).open(path)?;
+    file.set_len(len as u64)?;
+    file.sync_all()
+}
+
+fn parse_segment(path: &Path) -> Option<u64> {
+    if path.extension()? != SEGMENT_EXTENSION {
+        return None;
+    }
+    path.file_stem()?.to_str()?.parse::<u64>().ok()
+}
+
+fn encode_frame(record: &[u8]) -> Vec<u8> {
+    let mut frame = Vec::with_capacity(HEADER_SIZE + record.len());
+    frame.extend_from_slice(&(record.len() as u32).to_be_bytes());
+    frame.extend_from_slice(&crc32fast::hash(record).to_be_bytes());
+    frame.extend_from_slice(record);
+    frame
+}
+
+/// The first invalid frame of a segment.
+#[derive(Debug, PartialEq, Eq)]
+enum FrameError {
+    /// The last frame of the last segment is torn by a partial write.
+    Torn(usize),
+    /// The frame at the offset is corrupted for the reason.
+    Corrupted(usize, &'static str),
+}
+
+/// Decode frames with their offsets from the data of a segment, until the first invalid frame.
+/// An invalid frame is torn only if it is in the last segment and no valid frame follows it,
+/// since a partial write only leaves garbage at the tail of the Wal.
+fn decode_frames(data: &[u8], is_last: bool) -> (Vec<(usize, Bytes)>, Option<FrameError>) {
+    let mut frames = Vec::new();
+    let mut offset = 0usize;
+
+    while offset < data.len() {
+        let end = match check_frame(data, offset) {
+            Ok(end) => end,
+            Err(_) if is_last && !has_valid_frame(data, offset + 1) => {
+                return (frames, Some(FrameError::Torn(offset)));
+            }
+            Err(reason) => return (frames, Some(FrameError::Corrupted(offset, reason))),
+        };
+
+        frames.push((offset, Bytes::from(&data[offset + HEADER_SIZE..end])));
+        offset = end;
+    }
+    (frames, None)
+}
+
+/// Check the frame at the offset. Return the end of the frame, or the reason if it is invalid.
+fn check_frame(data: &[u8], offset: usize) -> Result<usize, &'static str> {
+    let end = frame_end(data, offset)?;
+    let checksum = read_u32(&data[offset + LENGTH_SIZE..]);
+
+    if crc32fast::hash(&data[offset + HEADER_SIZE..end]) != checksum {
+        return Err("checksum mismatch");
+    }
+    Ok(end)
+}
+
+/// Check the header of the frame at the offset. Return the end of the frame, or the reason if the
+/// header is invalid. Records are never empty, so a zero length is a zero-filled tail.
+fn frame_end(data: &[u8], offset: usize) -> Result<usize, &'static str> {
+    if offset + HEADER_SIZE > data.len() {
+        return Err("incomplete header");
+    }
+
+    let len = read_u32(&data[offset..]) as usize;
+    if len == 0 {
+        return Err("empty record");
+    }
+
+    let end = offset + HEADER_SIZE + len;
+    if end > data.len() {
+        return Err("length out of range");
+    }
+    Ok(end)
+}
+
+/// Whether frames with valid headers run from any offset after the given one to the end of the
+/// data. Only the headers are checked, so that the scan is linear in the size of the segment.
+fn has_valid_frame(data: &[u8], from: usize) -> bool {
+    if from >= data.len() {
+        return false;
+    }
+
+    // `chained[i]` is whether the frames from `from + i` run to the end of the data.
+    let mut chained = vec![false; data.len() - from + 1];
+    chained[data.len() - from] = true;
+    for offset in (from..data.len()).rev() {
+        chained[offset - from] = match frame_end(data, offset) {
+            Ok(end) => chained[end - from],
+            Err(_) => false,
+        };
+    }
+    chained[..data.len() - from].iter().any(|chained| *chained)
+}
+
+fn read_u32(data: &[u8]) -> u32 {
+    let mut tmp = [0u8; 4];
+    tmp.copy_from_slice(&data[..4]);
+    u32::from_be_bytes(tmp)
+}
+
+fn io_err(err: io::Error) -> Box<dyn Error + Send> {
+    Box::new(err)
+}
+
+fn wal_err(err: ConsensusError) -> Box<dyn Error + Send> {
+    Box::new(err)
+}
+
+#[cfg(test)]
+mod test {
+    use std::env::temp_dir;
+    use std::fs::{self, OpenOptions};
+    use std::io::Write;
+    use std::path::PathBuf;
+
+    use bytes::Bytes;
+    use rand::random;
+
+    use crate::{error::ConsensusError, WalStorage};
+
+    use super::{FileWalStorage, HEADER_SIZE};
+
+    fn gen_path() -> PathBuf {
+        temp_dir().join(format!("overlord_wal_{}", random::<u64>()))
+    }
+
+    fn gen_record() -> Bytes {
+        Bytes::from((0..64).map(|_| random::<u8>()).collect::<Vec<_>>())
+    }
+
+    #[runtime::test]
+    async fn test_file_storage() {
+        let path = gen_path();
+        let storage = FileWalStorage::new(path.to_str().unwrap(), 0);
+        assert!(storage.load().await.unwrap().is_empty());
+
+        let mut expect = Vec::new();
+        for epoch_id in 1..5u64 {
+            for _ in 0..2 {
+                let record = gen_record();
+                storage.append(epoch_id, record.clone()).await.unwrap();
+                expect.push(record);
+            }
+            assert!(path.join(format!("{}.wal", epoch_id)).exists());
+        }
+        assert_eq!(storage.load().await.unwrap(), expect);
+
+        // Segments of epoch 3 and 4 are kept.
+        storage.flush(3).await.unwrap();
+        assert!(!path.join("2.wal").exists());
+        assert_eq!(storage.load().await.unwrap(), expect.split_off(4));
+
+        // Records of a new epoch are appended to a new segment.
+        let record = gen_record();
+        storage.append(5, record.clone()).await.unwrap();
+        expect.push(record);
+        assert_eq!(storage.load().await.unwrap(), expect);
+    }
+
+    #[runtime::test]
+    async fn test_retention() {
+        let path = gen_path();
+        let storage = FileWalStorage::new(path.to_str().unwrap(), 2);
+        for epoch_id in 1..10u64 {
+            storage.append(epoch_id, gen_record()).await.unwrap();
+            storage.flush(epoch_id - 1).await.unwrap();
+        }
+
+        // Segments of epoch 6, 7, 8 and 9 are kept.
+        assert_eq!(storage.load().await.unwrap().len(), 4);
+        assert!(!path.join("5.wal").exists());
+        assert!(path.join("6.wal").exists());
+    }
+
+    #[runtime::test]
+    async fn test_torn_record() {
+        let path = gen_path();
+        let storage = FileWalStorage::new(path.to_str().unwrap(), 0);
+        let record = gen_record();
+        storage.append(1, record.clone()).await.unwrap();
+        let segment = path.join("1.wal");
+        let valid_len = fs::metadata(&segment).unwrap().len();
+
+        // An incomplete record at the tail is skipped, and loading does not modify the segment.
+        let mut file = OpenOptions::new().append(true).open(&segment).unwrap();
+        file.write_all(&[0u8, 0, 0, 64, 0, 0]).unwrap();
+        let storage = FileWalStorage::new(path.to_str().unwrap(), 0);
+        assert_eq!(storage.load().await.unwrap(), vec![record.clone()]);
+        assert_eq!(fs::metadata(&segment).unwrap().len(), valid_len + 6);
+
+        // The torn record is truncated before appending.
+        let new_record = gen_record();
+        storage.append(1, new_record.clone()).await.unwrap();
+        assert_eq!(storage.load().await.unwrap(), vec![
+            record.clone(),
+            new_record.clone()
+        ]);
+
+        // A complete record with a wrong checksum at the tail is torn.
+        let valid_len = fs::metadata(&segment).unwrap().len();
+        storage.append(1, gen_record()).await.unwrap();
+        let mut data = fs::read(&segment).unwrap();
+        let last = data.len() - 1;
+        data[last] ^= 0xff;
+        fs::write(&segment, &data).unwrap();
+        let storage = FileWalStorage::new(path.to_str().unwrap(), 0);
+        assert_eq!(storage.load().await.unwrap(), vec![
+            record.clone(),
+            new_record.clone()
+        ]);
+
+        // The torn record of the last segment is truncated before rotating to a new segment.
+        let next_record = gen_record();
+        storage.append(2, next_record.clone()).await.unwrap();
+        assert_eq!(fs::metadata(&segment).unwrap().len(), valid_len);
+        assert_eq!(storage.load().await.unwrap(), vec![
+            record,
+            new_record,
+            next_record
+        ]);
+    }
+
+    #[runtime::test]
+    async fn test_zero_filled_tail() {
+        let path = gen_path();
+        let storage = FileWalStorage::new(path.to_str().unwrap(), 0);
+        let records = vec![gen_record(), gen_record()];
+        for record in records.iter() {
+            storage.append(1, record.clone()).await.unwrap();
+        }
+        let segment = path.join("1.wal");
+        let valid_len = fs::metadata(&segment).unwrap().len();
+
+        // A tail that is extended but not written is zero-filled, which is torn rather than
+        // decoded as empty records.
+        let mut file = OpenOptions::new().append(true).open(&segment).unwrap();
+        file.write_all(&[0u8; 4 * HEADER_SIZE]).unwrap();
+        let storage = FileWalStorage::new(path.to_str().unwrap(), 0);
+        assert_eq!(storage.load().await.unwrap(), records);
+        assert_eq!(
+            fs::metadata(&segment).unwrap().len(),
+            valid_len + 4 * HEADER_SIZE as u64
+        );
+
+        // The zero-filled tail is truncated before appending.
+        let new_record = gen_record();
+        storage.append(1, new_record.clone()).await.unwrap();
+        let mut expect = records;
+        expect.push(new_record);
+        assert_eq!(storage.load().await.unwrap(), expect);
+    }
+
+    #[runtime::test]
+    async fn test_torn_record_not_last_segment() {
+        let path = gen_path();
+        let storage = FileWalStorage::new(path.to_str().unwrap(), 0);
+        storage.append(1, gen_record()).await.unwrap();
+        storage.append(2, gen_record()).await.unwrap();
+        let segment = path.join("1.wal");
+        let valid_len = fs::metadata(&segment).unwrap().len();
+
+        // Only the last segment can be torn, so this is corruption.
+        let mut file = OpenOptions::new().append(true).open(&segment).unwrap();
+        file.write_all(&[0u8, 0, 0, 64, 0, 0]).unwrap();
+        let err = storage.load().await.unwrap_err();
+        let err = err.downcast::<ConsensusError>().unwrap();
+        assert_eq!(*err, ConsensusError::WalCorrupted {
+            offset: valid_len,
+            reason: String::new(),
+        });
+    }
+
+    #[runtime::test]
+    async fn test_truncate() {
+        let path = gen_path();
+        let storage = FileWalStorage::new(path.to_str().unwrap(), 0);
+        let mut expect = Vec::new();
+        for epoch_id in 1..4u64 {
+            for _ in 0..2 {
+                let record = gen_record();
+                storage.append(epoch_id, record.clone()).await.unwrap();
+                expect.push(record);
+            }
+        }
+
+        // Keep the first 3 records.
+        let frames = storage.load_frames().unwrap();
+        assert_eq!(frames.len(), 6);
+        storage
+            .truncate(frames[3].epoch_id, frames[3].offset)
+            .unwrap();
+        expect.truncate(3);
+        assert_eq!(storage.load().await.unwrap(), expect);
+        assert!(!path.join("3.wal").exists());
+
+        // Append after truncating works well.
+        let record = gen_record();
+        storage.append(2, record.clone()).await.unwrap();
+        expect.push(record);
+        assert_eq!(storage.load().await.unwrap(), expect);
+    }
+
+    #[runtime::test]
+    async fn test_repair_corrupted_record() {
+        let path = gen_path();
+        let storage = FileWalStorage::new(path.to_str().unwrap(), 0);
+        let mut expect = Vec::new();
+        for epoch_id in 1..3u64 {
+            for _ in 0..3 {
+                let record = gen_record();
+                storage.append(epoch_id, record.clone()).await.unwrap();
+                expect.push(record);
+            }
+        }
+
+        // Corrupt the second record of epoch 1.
+        let segment = path.join("1.wal");
+        let mut data = fs::read(&segment).unwrap();
+        let offset = HEADER_SIZE + 64;
+        data[offset] = 0xff;
+        fs::write(&segment, &data).unwrap();
+        assert!(storage.load().await.is_err());
+
+        // The corrupted frame is loaded with the reason, and loading does not modify the Wal.
+        let frames = storage.load_frames().unwrap();
+        assert_eq!(frames.len(), 5);
+        assert_eq!(frames[0].record, Ok(expect[0].clone()));
+        assert_eq!(frames[1].epoch_id, 1);
+        assert_eq!(frames[1].offset, offset as u64);
+        assert!(frames[1].record.is_err());
+        assert_eq!(frames[2].epoch_id, 2);
+        assert_eq!(fs::read(&segment).unwrap(), data);
+
+        // Truncate from the corrupted frame.
+        storage
+            .truncate(frames[1].epoch_id, frames[1].offset)
+            .unwrap();
+        assert_eq!(storage.load().await.unwrap(), vec![expect[0].clone()]);
+    }
+
+    #[runtime::test]
+    async fn test_corrupted_record() {
+        let path = gen_path();
+        let storage = FileWalStorage::new(path.to_str().unwrap(), 0);
+        storage.append(1, gen_record()).await.unwrap();
+        storage.append(1, gen_record()).await.unwrap();
+
+        // Corrupt the first record.
+        let segment = path.join("1.wal");
+        let mut data = fs::read(&segment).unwrap();
+        data[HEADER_SIZE] ^= 0xff;
+        fs::write(&segment, &data).unwrap();
+
+        let err = storage.load().await.unwrap_err();
+        let err = err.downcast::<ConsensusError>().unwrap();
+        assert_eq!(*err, ConsensusError::WalCorrupted {
+            offset: 0,
+            reason: String::new(),
+        });
+    }
+
+    #[runtime::test]
+    async fn test_corrupted_length() {
+        let path = gen_path();
+        let storage = FileWalStorage::new(path.to_str().unwrap(), 0);
+        for _ in 0..3 {
+            storage.append(1, gen_record()).await.unwrap();
+        }
+
+        // Corrupt the length of the middle record, which runs past the end of the segment.
+        let segment = path.join("1.wal");
+        let mut data = fs::read(&segment).unwrap();
+        let offset = HEADER_SIZE + 64;
+        data[offset] = 0xff;
+        fs::write(&segment, &data).unwrap();
+
+        let err = storage.load().await.unwrap_err();
+        let err = err.downcast::<ConsensusError>().unwrap();
+        assert_eq!(*err, ConsensusError::WalCorrupted {
+            offset: offset as u64,
+            reason: String::new(),
+        });
+        assert_eq!(fs::read(&segment).unwrap(), data);
+    }
+}
@@ -0,0 +1,41 @@
+use std::error::Error;
+
+use async_trait::async_trait;
+use bytes::Bytes;
+use parking_lot::Mutex;
+
+use crate::WalStorage;
+
+/// An in-memory storage of the write ahead log. The records will be lost after restart, so it is
+/// only suitable for tests.
+#[derive(Debug, Default)]
+pub struct MemoryWalStorage(Mutex<Vec<(u64, Bytes)>>);
+
+impl MemoryWalStorage {
+    /// Create a new in-memory storage.
+    pub fn new() -> Self {
+        MemoryWalStorage(Mutex::new(Vec::new()))
+    }
+}
+
+#[async_trait]
+impl WalStorage for MemoryWalStorage {
+    async fn append(&self, epoch_id: u64, record: Bytes) -> Result<(), Box<dyn Error + Send>> {
+        self.0.lock().push((epoch_id, record));
+        Ok(())
+    }
+
+    async fn load(&self) -> Result<Vec<Bytes>, Box<dyn Error + Send>> {
+        Ok(self
+            .0
+            .lock()
+            .iter()
+            .map(|(_, record)| record.clone())
+            .collect::<Vec<_>>())
+    }
+
+    async fn flush(&self, till: u64) -> Result<(), Box<dyn Error + Send>> {
+        self.0.lock().retain(|(epoch_id, _)| *epoch_id >= till);
+        Ok(())
+    }
+}
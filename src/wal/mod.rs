@@ -0,0 +1,277 @@
+///
+mod file;
+///
+mod memory;
+
+pub use self::file::{FileWalStorage, WalFrame};
+pub use self::memory::MemoryWalStorage;
+
+use std::error::Error;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+
+use bytes::Bytes;
+use derive_more::Display;
+use log::debug;
+use rlp::Decodable;
+
+use crate::{error::ConsensusError, ConsensusResult, WalStorage, INIT_EPOCH_ID};
+
+/// Types of the messages that are saved into the Wal.
+#[derive(Clone, Debug, Display, PartialEq, Eq)]
+pub enum WalMsgType {
+    /// A signed proposal that self proposed or accepted.
+    #[display(fmt = "Signed Proposal")]
+    SignedProposal,
+    /// A signed vote that self voted.
+    #[display(fmt = "Signed Vote")]
+    SignedVote,
+    /// A quorum certificate of the current epoch ID and round.
+    #[display(fmt = "Aggregated Vote")]
+    AggregatedVote,
+    /// A commit decision.
+    #[display(fmt = "Commit")]
+    Commit,
+    /// A snapshot of the SMR status.
+    #[display(fmt = "SMR Status")]
+    SMRStatus,
+    /// A verified epoch hash that checked by `Consensus::check_epoch`.
+    #[display(fmt = "Check Epoch")]
+    CheckEpoch,
+    /// The last signed message that guards against double signing.
+    #[display(fmt = "Last Signed")]
+    LastSigned,
+}
+
+impl Into<u8> for WalMsgType {
+    fn into(self) -> u8 {
+        match self {
+            WalMsgType::SignedProposal => 0,
+            WalMsgType::SignedVote => 1,
+            WalMsgType::AggregatedVote => 2,
+            WalMsgType::Commit => 3,
+            WalMsgType::SMRStatus => 4,
+            WalMsgType::CheckEpoch => 5,
+            WalMsgType::LastSigned => 6,
+        }
+    }
+}
+
+impl WalMsgType {
+    /// Parse a Wal message type from a byte. Return `None` while the byte is invalid.
+    pub(crate) fn from_u8(s: u8) -> Option<Self> {
+        match s {
+            0 => Some(WalMsgType::SignedProposal),
+            1 => Some(WalMsgType::SignedVote),
+            2 => Some(WalMsgType::AggregatedVote),
+            3 => Some(WalMsgType::Commit),
+            4 => Some(WalMsgType::SMRStatus),
+            5 => Some(WalMsgType::CheckEpoch),
+            6 => Some(WalMsgType::LastSigned),
+            _ => None,
+        }
+    }
+}
+
+/// A record in the Wal. Each record consists of the message type, the epoch ID when the message
+/// is saved and the RLP encoded message.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct WalRecord {
+    /// The message type.
+    pub msg_type: WalMsgType,
+    /// The epoch ID when the message is saved.
+    pub epoch_id: u64,
+    /// The RLP encoded message.
+    pub msg: Vec<u8>,
+}
+
+impl WalRecord {
+    /// Decode the message of the record.
+    pub fn decode<D: Decodable>(&self) -> ConsensusResult<D> {
+        rlp::decode(&self.msg).map_err(|err| {
+            ConsensusError::WalErr(format!("decode {} message error {:?}", self.msg_type, err))
+        })
+    }
+}
+
+/// A write ahead log that saves RLP encoded records into the given storage. Only the records of
+/// the current epoch and the last epoch are kept, the others will be flushed as going to a new
+/// epoch.
+#[derive(Debug)]
+pub struct Wal<W: WalStorage> {
+    storage:  Arc<W>,
+    epoch_id: AtomicU64,
+}
+
+impl<W: WalStorage> Wal<W> {
+    /// Create a new Wal struct with the storage.
+    pub fn new(storage: Arc<W>) -> Self {
+        Wal {
+            storage,
+            epoch_id: AtomicU64::new(INIT_EPOCH_ID),
+        }
+    }
+
+    /// Set a new epoch of Wal, while go to new epoch. The records that epoch ID is lower than
+    /// `epoch_id - 1` will be flushed.
+    pub async fn set_epoch(&self, epoch_id: u64) -> ConsensusResult<()> {
+        self.set_epoch_with(epoch_id, Vec::new()).await
+    }
+
+    /// Set a new epoch of Wal as `set_epoch`, and save the given messages into the new epoch
+    /// before flushing, so that they are never flushed with the outdated records. Nothing is
+    /// saved if the epoch ID is not higher than the current.
+    pub async fn set_epoch_with(
+        &self,
+        epoch_id: u64,
+        msgs: Vec<(WalMsgType, Vec<u8>)>,
+    ) -> ConsensusResult<()> {
+        if epoch_id <= self.epoch_id.load(Ordering::Acquire) {
+            return Ok(());
+        }
+
+        debug!("Overlord: Wal set epoch {}", epoch_id);
+        self.epoch_id.store(epoch_id, Ordering::Release);
+        for (msg_type, msg) in msgs.into_iter() {
+            self.save(msg_type, msg).await?;
+        }
+        self.storage
+            .flush(epoch_id.saturating_sub(1))
+            .await
+            .map_err(wal_err)
+    }
+
+    /// Save message to Wal.
+    pub async fn save(&self, msg_type: WalMsgType, msg: Vec<u8>) -> ConsensusResult<()> {
+        let epoch_id = self.epoch_id.load(Ordering::Acquire);
+        let record = WalRecord {
+            msg_type,
+            epoch_id,
+            msg,
+        };
+
+        self.storage
+            .append(epoch_id, Bytes::from(rlp::encode(&record)))
+            .await
+            .map_err(wal_err)
+    }
+
+    /// Load message from Wal.
+    pub async fn load(&self) -> ConsensusResult<Vec<(WalMsgType, Vec<u8>)>> {
+        Ok(self
+            .load_records()
+            .await?
+            .into_iter()
+            .map(|record| (record.msg_type, record.msg))
+            .collect::<Vec<_>>())
+    }
+
+    /// Load all records with its epoch ID from the storage.
+    pub async fn load_records(&self) -> ConsensusResult<Vec<WalRecord>> {
+        self.storage
+            .load()
+            .await
+            .map_err(wal_err)?
+            .iter()
+            .enumerate()
+            .map(|(index, data)| {
+                rlp::decode(data).map_err(|err| {
+                    ConsensusError::WalErr(format!("decode record {} error {:?}", index, err))
+                })
+            })
+            .collect::<ConsensusResult<Vec<_>>>()
+    }
+}
+
+/// Convert a storage error into consensus error. If the storage returns a consensus error, such
+/// as `ConsensusError::WalCorrupted`, keep it as is.
+fn wal_err(err: Box<dyn Error + Send>) -> ConsensusError {
+    match err.downcast::<ConsensusError>() {
+        Ok(err) => *err,
+        Err(err) => ConsensusError::WalErr(format!("{:?}", err)),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::sync::Arc;
+
+    use rand::random;
+
+    use super::{MemoryWalStorage, Wal, WalMsgType};
+
+    fn gen_wal() -> Wal<MemoryWalStorage> {
+        Wal::new(Arc::new(MemoryWalStorage::new()))
+    }
+
+    fn gen_msg() -> Vec<u8> {
+        (0..64).map(|_| random::<u8>()).collect::<Vec<_>>()
+    }
+
+    #[runtime::test]
+    async fn test_save_and_load() {
+        let wal = gen_wal();
+        assert!(wal.load().await.unwrap().is_empty());
+
+        let mut expect = Vec::new();
+        for msg_type in vec![
+            WalMsgType::SignedProposal,
+            WalMsgType::SignedVote,
+            WalMsgType::AggregatedVote,
+            WalMsgType::Commit,
+            WalMsgType::SMRStatus,
+            WalMsgType::CheckEpoch,
+            WalMsgType::LastSigned,
+        ]
+        .into_iter()
+        {
+            let msg = gen_msg();
+            wal.save(msg_type.clone(), msg.clone()).await.unwrap();
+            expect.push((msg_type, msg));
+        }
+        assert_eq!(wal.load().await.unwrap(), expect);
+    }
+
+    #[runtime::test]
+    async fn test_set_epoch() {
+        let wal = gen_wal();
+        let mut expect = Vec::new();
+
+        for epoch_id in 1..5u64 {
+            wal.set_epoch(epoch_id).await.unwrap();
+            let msg = gen_msg();
+            wal.save(WalMsgType::SignedVote, msg.clone()).await.unwrap();
+            expect.push((WalMsgType::SignedVote, msg));
+        }
+
+        // Records of epoch 3 and 4 are kept.
+        assert_eq!(wal.load().await.unwrap(), expect.split_off(2));
+
+        // Set a lower epoch ID does nothing.
+        wal.set_epoch(1).await.unwrap();
+        assert_eq!(wal.load().await.unwrap().len(), 2);
+    }
+
+    #[runtime::test]
+    async fn test_set_epoch_with() {
+        let wal = gen_wal();
+        let msg = gen_msg();
+        wal.set_epoch(1).await.unwrap();
+        wal.save(WalMsgType::LastSigned, msg.clone()).await.unwrap();
+
+        // The message is carried to each new epoch, so it is never flushed.
+        for epoch_id in 2..5u64 {
+            let keep = vec![(WalMsgType::LastSigned, msg.clone())];
+            wal.set_epoch_with(epoch_id, keep).await.unwrap();
+        }
+        let records = wal.load_records().await.unwrap();
+        assert_eq!(records.len(), 2);
+        assert_eq!(records[1].epoch_id, 4);
+        assert_eq!(records[1].msg, msg);
+
+        // Set a lower epoch ID saves nothing.
+        let keep = vec![(WalMsgType::LastSigned, msg.clone())];
+        wal.set_epoch_with(3, keep).await.unwrap();
+        assert_eq!(wal.load().await.unwrap().len(), 2);
+    }
+}
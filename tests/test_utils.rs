@@ -81,6 +81,7 @@ impl Consensus<Pill, Pill> for ConsensusHelper<Pill> {
         let status = Status {
             epoch_id:       epoch_id + 1,
             interval:       None,
+            timer_config:   None,
             authority_list: self.auth_list.clone(),
         };
         Ok(status)